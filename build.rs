@@ -1,4 +1,6 @@
 fn main() {
-    println!("cargo:rustc-link-lib=cap");
-    println!("cargo:rustc-link-search=native=/lib");
+    println!("cargo:rerun-if-env-changed=CARGO_FEATURE_LIBCAP");
+    if std::env::var_os("CARGO_FEATURE_LIBCAP").is_some() {
+        println!("cargo:rustc-link-lib=cap");
+    }
 }
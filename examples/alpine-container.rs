@@ -4,6 +4,7 @@ use simplelog::*;
 use tin_can::container::{
     step::{
         mount_namespace::{MountNamespace, MountOperation},
+        net_namespace::NetNamespace,
         pid_namespace::PIDNamespace,
         run_command::RunCommand,
         switch_working_directory::SwitchWorkingDirectory,
@@ -38,8 +39,9 @@ fn main() {
         .stderr(Stdio::inherit())
         .stdin(Stdio::inherit());
     let container = ContainerBuilder::new(UserNamespaceRoot::new_with_current_user_as_root(
-        PIDNamespace::new(MountNamespace::new(
-            MountOperation::switch_root_with_overlay(
+        // sysfs can only be mounted in a network namespace owned by the user namespace
+        PIDNamespace::new(NetNamespace::new(MountNamespace::new(
+            MountOperation::switch_root_with_overlay_and_pseudo_fs(
                 &test_dir.join("alpine"),
                 &test_dir.join("alpine-upper"),
                 &test_dir.join("work"),
@@ -47,7 +49,7 @@ fn main() {
                 &std::path::PathBuf::from("put-old"),
             ),
             SwitchWorkingDirectory::new("/".into(), RunCommand::new(command)),
-        )),
+        ))),
     ))
     .run()
     .unwrap();
@@ -1,15 +1,24 @@
 mod builder;
 mod context;
+mod resource_usage;
 pub mod step;
 use std::io::BufRead;
 
 pub use builder::*;
 pub use context::*;
+pub use resource_usage::*;
 
 use crate::linux;
 
 pub struct Container {
     ctx: Context,
+    resource_usage: ResourceUsage,
+}
+
+impl Container {
+    pub fn resource_usage(&self) -> &ResourceUsage {
+        &self.resource_usage
+    }
 }
 
 pub trait MapType {
@@ -1,6 +1,6 @@
 use crate::container::step::Step;
 
-use super::{Container, Context};
+use super::{ChildrenUsage, Container, Context, ResourceUsage};
 
 pub struct ContainerBuilder<C> {
     component: C,
@@ -21,7 +21,15 @@ where
 {
     pub fn run(self) -> Result<Container, C::Error> {
         let mut ctx = Context::default();
+        let before = ChildrenUsage::collect();
         self.component.run(&mut ctx)?;
-        Ok(Container { ctx })
+        let resource_usage = ResourceUsage {
+            cgroup: ctx.take_cgroup_usage(),
+            children: ChildrenUsage::collect().since(&before),
+        };
+        Ok(Container {
+            ctx,
+            resource_usage,
+        })
     }
 }
@@ -1,5 +1,7 @@
 use crate::linux;
 
+use super::CgroupUsage;
+
 #[derive(Default, Debug)]
 pub struct Context {
     cgroup: bool,
@@ -11,6 +13,13 @@ pub struct Context {
     user: bool,
     uts: bool,
     pid_fd: Option<std::fs::File>,
+    cgroup_usage: Option<CgroupUsage>,
+    seccomp: Vec<linux::seccomp::Program>,
+    landlock: Vec<linux::landlock::Ruleset>,
+    parent_death_signal: Option<libc::c_int>,
+    idmapped_mounts: Vec<(std::path::PathBuf, linux::mount_api::Mount)>,
+    #[cfg(feature = "cap")]
+    capabilities: Option<linux::libcap::CapabilitySets>,
 }
 
 impl Context {
@@ -54,12 +63,75 @@ impl Context {
         self.set_pidfd();
     }
 
+    pub(crate) fn set_cgroup_usage(&mut self, usage: CgroupUsage) {
+        self.cgroup_usage = Some(usage);
+    }
+
+    pub(crate) fn take_cgroup_usage(&mut self) -> Option<CgroupUsage> {
+        self.cgroup_usage.take()
+    }
+
+    pub(crate) fn add_seccomp_program(&mut self, program: linux::seccomp::Program) {
+        self.seccomp.push(program);
+    }
+
+    pub(crate) fn seccomp_programs(&self) -> &[linux::seccomp::Program] {
+        &self.seccomp
+    }
+
+    pub(crate) fn add_landlock_ruleset(&mut self, ruleset: linux::landlock::Ruleset) {
+        self.landlock.push(ruleset);
+    }
+
+    pub(crate) fn landlock_rulesets(&self) -> &[linux::landlock::Ruleset] {
+        &self.landlock
+    }
+
+    pub(crate) fn set_parent_death_signal(&mut self, signal: libc::c_int) {
+        self.parent_death_signal = Some(signal);
+    }
+
+    pub(crate) fn parent_death_signal(&self) -> Option<libc::c_int> {
+        self.parent_death_signal
+    }
+
+    pub(crate) fn add_idmapped_mount(
+        &mut self,
+        src: std::path::PathBuf,
+        mount: linux::mount_api::Mount,
+    ) {
+        self.idmapped_mounts.push((src, mount));
+    }
+
+    pub(crate) fn take_idmapped_mount(
+        &mut self,
+        src: &std::path::Path,
+    ) -> Option<linux::mount_api::Mount> {
+        let index = self
+            .idmapped_mounts
+            .iter()
+            .position(|(path, _)| path == src)?;
+        Some(self.idmapped_mounts.remove(index).1)
+    }
+
+    #[cfg(feature = "cap")]
+    pub(crate) fn set_capabilities(&mut self, capabilities: linux::libcap::CapabilitySets) {
+        self.capabilities = Some(capabilities);
+    }
+
+    #[cfg(feature = "cap")]
+    pub(crate) fn capabilities(&self) -> Option<&linux::libcap::CapabilitySets> {
+        self.capabilities.as_ref()
+    }
+
     fn set_pidfd(&mut self) {
-        let pidfd = linux::pidfd_open(std::process::id());
-        self.pid_fd = Some(pidfd);
+        match linux::pidfd_open(std::process::id()) {
+            Ok(pidfd) => self.pid_fd = Some(pidfd),
+            Err(e) => log::warn!("Failed to open pidfd: {e}"),
+        }
     }
 
-    pub fn cgroup(&mut self) -> bool {
+    pub fn cgroup(&self) -> bool {
         self.cgroup
     }
 
@@ -0,0 +1,196 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::linux::{self, cgroup::Cgroup};
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// Usage recorded by the cgroup of the container. Only available if the container was run
+    /// in its own cgroup.
+    pub cgroup: Option<CgroupUsage>,
+    /// Usage of the processes of the container, measured as the growth of the usage of all
+    /// waited for children of the calling process while the container was running.
+    pub children: ChildrenUsage,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CgroupUsage {
+    pub memory: Option<MemoryUsage>,
+    pub cpu: Option<CpuUsage>,
+    pub io: Vec<IoUsage>,
+    pub pids_peak: Option<u64>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Highest memory usage in bytes.
+    pub peak: Option<u64>,
+    /// Content of `memory.stat`.
+    pub stat: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CpuUsage {
+    pub usage: Duration,
+    pub user: Duration,
+    pub system: Duration,
+    pub nr_periods: u64,
+    pub nr_throttled: u64,
+    pub throttled: Duration,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct IoUsage {
+    pub major: u32,
+    pub minor: u32,
+    pub rbytes: u64,
+    pub wbytes: u64,
+    pub rios: u64,
+    pub wios: u64,
+    pub dbytes: u64,
+    pub dios: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChildrenUsage {
+    pub user_time: Duration,
+    pub system_time: Duration,
+    /// Largest resident set size of a single child in bytes. `0` if no process of the container
+    /// used more memory than the children waited for before the container was started.
+    pub max_rss: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+    pub block_input: u64,
+    pub block_output: u64,
+    pub voluntary_context_switches: u64,
+    pub involuntary_context_switches: u64,
+}
+
+impl CgroupUsage {
+    pub(crate) fn read(cgroup: &Cgroup) -> Self {
+        Self {
+            memory: read_memory(cgroup),
+            cpu: cgroup
+                .read("cpu.stat")
+                .ok()
+                .map(|stat| parse_cpu_stat(&stat)),
+            io: cgroup
+                .read("io.stat")
+                .map(|stat| parse_io_stat(&stat))
+                .unwrap_or_default(),
+            pids_peak: cgroup
+                .read("pids.peak")
+                .ok()
+                .and_then(|peak| peak.trim().parse().ok()),
+        }
+    }
+}
+
+impl ChildrenUsage {
+    pub(crate) fn collect() -> Self {
+        let usage = linux::children_rusage();
+        let duration =
+            |time: libc::timeval| Duration::new(time.tv_sec as u64, time.tv_usec as u32 * 1000);
+        Self {
+            user_time: duration(usage.ru_utime),
+            system_time: duration(usage.ru_stime),
+            max_rss: usage.ru_maxrss as u64 * 1024,
+            minor_faults: usage.ru_minflt as u64,
+            major_faults: usage.ru_majflt as u64,
+            block_input: usage.ru_inblock as u64,
+            block_output: usage.ru_oublock as u64,
+            voluntary_context_switches: usage.ru_nvcsw as u64,
+            involuntary_context_switches: usage.ru_nivcsw as u64,
+        }
+    }
+
+    /// Usage added since `before` was collected.
+    pub(crate) fn since(&self, before: &Self) -> Self {
+        Self {
+            user_time: self.user_time.saturating_sub(before.user_time),
+            system_time: self.system_time.saturating_sub(before.system_time),
+            // The largest child is only known if it exceeds the previous maximum
+            max_rss: if self.max_rss > before.max_rss {
+                self.max_rss
+            } else {
+                0
+            },
+            minor_faults: self.minor_faults.saturating_sub(before.minor_faults),
+            major_faults: self.major_faults.saturating_sub(before.major_faults),
+            block_input: self.block_input.saturating_sub(before.block_input),
+            block_output: self.block_output.saturating_sub(before.block_output),
+            voluntary_context_switches: self
+                .voluntary_context_switches
+                .saturating_sub(before.voluntary_context_switches),
+            involuntary_context_switches: self
+                .involuntary_context_switches
+                .saturating_sub(before.involuntary_context_switches),
+        }
+    }
+}
+
+fn read_memory(cgroup: &Cgroup) -> Option<MemoryUsage> {
+    let stat = cgroup.read("memory.stat").ok()?;
+    Some(MemoryUsage {
+        peak: cgroup
+            .read("memory.peak")
+            .ok()
+            .and_then(|peak| peak.trim().parse().ok()),
+        stat: parse_flat_keyed(&stat).collect(),
+    })
+}
+
+fn parse_flat_keyed(content: &str) -> impl Iterator<Item = (String, u64)> + '_ {
+    content.lines().filter_map(|line| {
+        let (key, value) = line.split_once(' ')?;
+        Some((key.to_string(), value.trim().parse().ok()?))
+    })
+}
+
+fn parse_cpu_stat(content: &str) -> CpuUsage {
+    let mut usage = CpuUsage::default();
+    for (key, value) in parse_flat_keyed(content) {
+        match key.as_str() {
+            "usage_usec" => usage.usage = Duration::from_micros(value),
+            "user_usec" => usage.user = Duration::from_micros(value),
+            "system_usec" => usage.system = Duration::from_micros(value),
+            "nr_periods" => usage.nr_periods = value,
+            "nr_throttled" => usage.nr_throttled = value,
+            "throttled_usec" => usage.throttled = Duration::from_micros(value),
+            _ => {}
+        }
+    }
+    usage
+}
+
+fn parse_io_stat(content: &str) -> Vec<IoUsage> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let (major, minor) = parts.next()?.split_once(':')?;
+            let mut usage = IoUsage {
+                major: major.parse().ok()?,
+                minor: minor.parse().ok()?,
+                ..Default::default()
+            };
+            for part in parts {
+                let Some((key, value)) = part.split_once('=') else {
+                    continue;
+                };
+                let Ok(value) = value.parse() else {
+                    continue;
+                };
+                match key {
+                    "rbytes" => usage.rbytes = value,
+                    "wbytes" => usage.wbytes = value,
+                    "rios" => usage.rios = value,
+                    "wios" => usage.wios = value,
+                    "dbytes" => usage.dbytes = value,
+                    "dios" => usage.dios = value,
+                    _ => {}
+                }
+            }
+            Some(usage)
+        })
+        .collect()
+}
@@ -1,11 +1,27 @@
 use super::Context;
 
+#[cfg(feature = "cap")]
+pub mod capabilities;
+pub mod cgroup;
+pub mod cgroup_namespace;
+pub mod hardening;
+pub mod ipc_namespace;
+pub mod landlock;
 pub mod mount_namespace;
+pub mod net_namespace;
 pub mod pid_namespace;
+pub mod rlimits;
 pub mod run_command;
+pub mod scheduling;
+pub mod seccomp;
+pub mod seccomp_notify;
 pub mod switch_user;
 pub mod switch_working_directory;
+pub mod sysctl;
+pub mod time_namespace;
 pub mod user_namespace;
+pub mod uts_namespace;
+pub mod veth_network;
 
 pub trait Step {
     type Error: std::error::Error;
@@ -0,0 +1,143 @@
+use crate::{
+    container::{step::Step, Context},
+    linux::libcap,
+};
+
+pub use crate::linux::libcap::Capability;
+
+/// Sets the capability sets of the process running the next steps and of the command.
+///
+/// Dropping capabilities from the bounding set requires `CAP_SETPCAP` in the effective set, so
+/// this step must run after [`UserNamespaceRoot`](super::user_namespace::UserNamespaceRoot) and
+/// before [`SwitchUser`](super::switch_user::SwitchUser), which clears the effective set.
+/// `PR_SET_KEEPCAPS` is set, so the permitted set survives switching to a non root user, and
+/// [`RunCommand`](super::run_command::RunCommand) restores the effective, inheritable and
+/// ambient sets right before the command is executed. `SwitchUser` itself needs
+/// [`Capability::SETUID`] and [`Capability::SETGID`] in the effective set.
+///
+/// Note that a process with uid 0 gains every capability of the bounding set on `execve`, so for
+/// root the bounding set is what limits the command.
+pub struct Capabilities<S>
+where
+    S: Step,
+{
+    effective: Vec<Capability>,
+    permitted: Vec<Capability>,
+    inheritable: Vec<Capability>,
+    bounding: Vec<Capability>,
+    ambient: Vec<Capability>,
+    next: S,
+}
+
+impl<S> Capabilities<S>
+where
+    S: Step,
+{
+    /// Keeps only `capabilities` in every set. Because of the ambient set, commands which are
+    /// run as a non root user keep them as well.
+    pub fn new(capabilities: Vec<Capability>, next: S) -> Self {
+        Self {
+            effective: capabilities.clone(),
+            permitted: capabilities.clone(),
+            inheritable: capabilities.clone(),
+            bounding: capabilities.clone(),
+            ambient: capabilities,
+            next,
+        }
+    }
+
+    pub fn with_effective(mut self, capabilities: Vec<Capability>) -> Self {
+        self.effective = capabilities;
+        self
+    }
+
+    pub fn with_permitted(mut self, capabilities: Vec<Capability>) -> Self {
+        self.permitted = capabilities;
+        self
+    }
+
+    pub fn with_inheritable(mut self, capabilities: Vec<Capability>) -> Self {
+        self.inheritable = capabilities;
+        self
+    }
+
+    pub fn with_bounding(mut self, capabilities: Vec<Capability>) -> Self {
+        self.bounding = capabilities;
+        self
+    }
+
+    pub fn with_ambient(mut self, capabilities: Vec<Capability>) -> Self {
+        self.ambient = capabilities;
+        self
+    }
+
+    fn validate<E>(&self) -> Result<(), CapabilitiesError<E>>
+    where
+        E: std::error::Error,
+    {
+        if let Some(cap) = self
+            .effective
+            .iter()
+            .find(|cap| !self.permitted.contains(cap))
+        {
+            return Err(CapabilitiesError::NotPermitted("effective", *cap));
+        }
+        if let Some(cap) = self
+            .ambient
+            .iter()
+            .find(|cap| !self.permitted.contains(cap) || !self.inheritable.contains(cap))
+        {
+            return Err(CapabilitiesError::NotPermitted("ambient", *cap));
+        }
+        Ok(())
+    }
+}
+
+impl<S> Step for Capabilities<S>
+where
+    S: Step,
+{
+    type Error = CapabilitiesError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        log::trace!("Set capabilities");
+        self.validate()?;
+        for cap in Capability::ALL {
+            if self.bounding.contains(&cap) || !cap.is_supported() {
+                continue;
+            }
+            log::debug!("Drop {cap:?} from bounding set");
+            libcap::drop_bounding(cap).map_err(|e| CapabilitiesError::Bounding(cap, e))?;
+        }
+        libcap::set_keep_caps().map_err(CapabilitiesError::KeepCaps)?;
+        libcap::set_proc(&self.effective, &self.permitted, &self.inheritable)
+            .map_err(CapabilitiesError::Set)?;
+        libcap::set_ambient(&self.ambient).map_err(CapabilitiesError::Ambient)?;
+        ctx.set_capabilities(libcap::CapabilitySets {
+            effective: self.effective,
+            permitted: self.permitted,
+            inheritable: self.inheritable,
+            ambient: self.ambient,
+        });
+        self.next.run(ctx).map_err(CapabilitiesError::ChildError)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CapabilitiesError<E>
+where
+    E: std::error::Error,
+{
+    #[error("{1:?} is in the {0} set, but not permitted")]
+    NotPermitted(&'static str, Capability),
+    #[error("Failed to drop {0:?} from the bounding set: {1}")]
+    Bounding(Capability, std::io::Error),
+    #[error("Failed to keep capabilities on user change: {0}")]
+    KeepCaps(std::io::Error),
+    #[error("Failed to set capabilities: {0}")]
+    Set(std::io::Error),
+    #[error("Failed to set ambient capabilities: {0}")]
+    Ambient(std::io::Error),
+    #[error(transparent)]
+    ChildError(E),
+}
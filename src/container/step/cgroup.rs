@@ -0,0 +1,391 @@
+mod events;
+
+use std::{path::PathBuf, time::Duration};
+
+pub use events::*;
+
+use crate::{
+    container::{step::Step, CgroupUsage, Context},
+    linux::{self, cgroup},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Max,
+    Value(u64),
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Max => write!(f, "max"),
+            Limit::Value(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuMax {
+    pub quota: Limit,
+    pub period: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IoMax {
+    pub major: u32,
+    pub minor: u32,
+    pub rbps: Limit,
+    pub wbps: Limit,
+    pub riops: Limit,
+    pub wiops: Limit,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Resources {
+    pub memory_max: Option<Limit>,
+    pub memory_swap_max: Option<Limit>,
+    pub cpu_max: Option<CpuMax>,
+    pub cpu_weight: Option<u16>,
+    pub pids_max: Option<Limit>,
+    pub io_max: Vec<IoMax>,
+}
+
+impl Resources {
+    fn controllers(&self) -> Vec<&'static str> {
+        let mut controllers = Vec::new();
+        if self.memory_max.is_some() || self.memory_swap_max.is_some() {
+            controllers.push("memory");
+        }
+        if self.cpu_max.is_some() || self.cpu_weight.is_some() {
+            controllers.push("cpu");
+        }
+        if self.pids_max.is_some() {
+            controllers.push("pids");
+        }
+        if !self.io_max.is_empty() {
+            controllers.push("io");
+        }
+        controllers
+    }
+
+    fn apply(&self, cgroup: &cgroup::Cgroup) -> Result<(), CgroupSetupError> {
+        fn write(
+            cgroup: &cgroup::Cgroup,
+            file: &'static str,
+            value: String,
+        ) -> Result<(), CgroupSetupError> {
+            log::debug!("Set {file} to {value}");
+            cgroup
+                .write(file, value)
+                .map_err(|error| CgroupSetupError::Limit { file, error })
+        }
+        if let Some(memory_max) = self.memory_max {
+            write(cgroup, "memory.max", memory_max.to_string())?;
+        }
+        if let Some(memory_swap_max) = self.memory_swap_max {
+            write(cgroup, "memory.swap.max", memory_swap_max.to_string())?;
+        }
+        if let Some(CpuMax { quota, period }) = self.cpu_max {
+            write(cgroup, "cpu.max", format!("{quota} {period}"))?;
+        }
+        if let Some(cpu_weight) = self.cpu_weight {
+            write(cgroup, "cpu.weight", cpu_weight.to_string())?;
+        }
+        if let Some(pids_max) = self.pids_max {
+            write(cgroup, "pids.max", pids_max.to_string())?;
+        }
+        for io in &self.io_max {
+            write(
+                cgroup,
+                "io.max",
+                format!(
+                    "{}:{} rbps={} wbps={} riops={} wiops={}",
+                    io.major, io.minor, io.rbps, io.wbps, io.riops, io.wiops
+                ),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the next step in a new cgroup `name` below the delegated cgroup `parent` and applies the
+/// configured resource limits to it.
+///
+/// The process running the next step moves itself into the new cgroup before doing anything else,
+/// so all processes it creates are accounted to the cgroup. Because of the "no internal processes"
+/// rule of cgroup v2 `parent` should not contain any processes.
+///
+/// After the next step has finished, processes left in the cgroup are stopped, the resources used
+/// are recorded in [`ResourceUsage`](crate::container::ResourceUsage) and the cgroup is removed.
+pub struct Cgroup<S>
+where
+    S: Step,
+{
+    next: S,
+    parent: PathBuf,
+    name: String,
+    resources: Resources,
+    stop_timeout: Duration,
+    events: Option<(Vec<PressureTrigger>, EventCallback)>,
+}
+
+type EventCallback = Box<dyn FnMut(CgroupEvent) + Send>;
+
+impl<S> Cgroup<S>
+where
+    S: Step,
+{
+    pub fn new(parent: PathBuf, name: String, resources: Resources, next: S) -> Self {
+        Self {
+            next,
+            parent,
+            name,
+            resources,
+            stop_timeout: DEFAULT_STOP_TIMEOUT,
+            events: None,
+        }
+    }
+
+    /// Calls `callback` for OOM events and whenever one of the pressure `triggers` fires while
+    /// the container is running.
+    pub fn with_event_callback<F>(mut self, triggers: Vec<PressureTrigger>, callback: F) -> Self
+    where
+        F: FnMut(CgroupEvent) + Send + 'static,
+    {
+        self.events = Some((triggers, Box::new(callback)));
+        self
+    }
+
+    /// Time to wait after sending `SIGTERM` to processes which are left over when the next step
+    /// has finished, before they are killed.
+    pub fn with_stop_timeout(mut self, stop_timeout: Duration) -> Self {
+        self.stop_timeout = stop_timeout;
+        self
+    }
+
+    /// Returns a handle that can be used to control the container while it is running, e.g. from
+    /// another thread.
+    pub fn handle(&self) -> CgroupHandle {
+        CgroupHandle {
+            path: self.parent.join(&self.name),
+        }
+    }
+}
+
+const DEFAULT_FREEZE_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgroupHandle {
+    path: PathBuf,
+}
+
+impl CgroupHandle {
+    fn cgroup(&self) -> std::io::Result<cgroup::Cgroup> {
+        cgroup::Cgroup::open(&self.path)
+    }
+
+    /// Reads the current resource usage of the container.
+    pub fn resource_usage(&self) -> std::io::Result<CgroupUsage> {
+        Ok(CgroupUsage::read(&self.cgroup()?))
+    }
+
+    /// Stops all processes of the container and waits until the cgroup reports them as frozen.
+    pub fn freeze(&self) -> std::io::Result<()> {
+        self.freeze_timeout(DEFAULT_FREEZE_TIMEOUT)
+    }
+
+    pub fn freeze_timeout(&self, timeout: Duration) -> std::io::Result<()> {
+        self.cgroup()?.set_frozen(true, timeout)
+    }
+
+    /// Resumes all processes of a frozen container.
+    pub fn thaw(&self) -> std::io::Result<()> {
+        self.thaw_timeout(DEFAULT_FREEZE_TIMEOUT)
+    }
+
+    pub fn thaw_timeout(&self, timeout: Duration) -> std::io::Result<()> {
+        self.cgroup()?.set_frozen(false, timeout)
+    }
+
+    pub fn is_frozen(&self) -> std::io::Result<bool> {
+        self.cgroup()?.is_frozen()
+    }
+
+    /// Calls `callback` on a background thread for OOM events and whenever one of the pressure
+    /// `triggers` fires. Events are delivered until the returned watcher is dropped or the
+    /// container has finished.
+    pub fn watch_events<F>(
+        &self,
+        triggers: &[PressureTrigger],
+        callback: F,
+    ) -> std::io::Result<EventWatcher>
+    where
+        F: FnMut(CgroupEvent) + Send + 'static,
+    {
+        EventWatcher::start(&self.cgroup()?, triggers, callback)
+    }
+
+    /// Like [`Self::watch_events`] but delivers the events to a channel.
+    pub fn events(
+        &self,
+        triggers: &[PressureTrigger],
+    ) -> std::io::Result<(EventWatcher, std::sync::mpsc::Receiver<CgroupEvent>)> {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let watcher = self.watch_events(triggers, move |event| {
+            let _ = tx.send(event);
+        })?;
+        Ok((watcher, rx))
+    }
+
+    /// Sends `signal` to every process in the container.
+    pub fn kill_all(&self, signal: libc::c_int) -> std::io::Result<()> {
+        self.cgroup()?.signal_all(signal)
+    }
+
+    /// Sends `SIGTERM` to every process in the container and kills the processes which are still
+    /// running after `timeout`.
+    pub fn stop(&self, timeout: Duration) -> std::io::Result<()> {
+        let cgroup = self.cgroup()?;
+        if cgroup.is_frozen()? {
+            cgroup.set_frozen(false, DEFAULT_FREEZE_TIMEOUT)?;
+        }
+        cgroup.stop(timeout)
+    }
+}
+
+impl<S> Step for Cgroup<S>
+where
+    S: Step,
+{
+    type Error = CgroupError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        let cgroup = setup_cgroup(&self.parent, &self.name, &self.resources)?;
+        let watcher = match self.events {
+            Some((triggers, callback)) => match EventWatcher::start(&cgroup, &triggers, callback) {
+                Ok(watcher) => Some(watcher),
+                Err(e) => {
+                    if let Err(e) = cgroup.remove() {
+                        log::warn!("Failed to remove cgroup: {e}");
+                    }
+                    return Err(CgroupSetupError::Events(e).into());
+                }
+            },
+            None => None,
+        };
+        let shared_data = SharedData {
+            next: Some(self.next),
+            cgroup: &cgroup,
+            ctx,
+        };
+        let mut join_handle = linux::clone_vm_with_namespaces(0, cgroup_vm, shared_data)?;
+        log::info!("Cgroup process {}", join_handle.pid);
+        join_handle.set_cgroup(cgroup.clone());
+        join_handle.set_stop_timeout(self.stop_timeout);
+        // The process does not return a result if it was killed
+        let res = join_handle.join().unwrap_or(Err(CgroupError::Killed));
+        if let Err(e) = cgroup.stop(self.stop_timeout) {
+            log::error!("Failed to stop remaining processes: {e}");
+        }
+        drop(watcher);
+        ctx.set_cgroup_usage(CgroupUsage::read(&cgroup));
+        if let Err(e) = cgroup.remove() {
+            log::warn!("Failed to remove cgroup: {e}");
+        }
+        res
+    }
+}
+
+fn setup_cgroup(
+    parent: &std::path::Path,
+    name: &str,
+    resources: &Resources,
+) -> Result<cgroup::Cgroup, CgroupSetupError> {
+    let parent = cgroup::Cgroup::open(parent).map_err(CgroupSetupError::Open)?;
+    let controllers = resources.controllers();
+    let available = parent
+        .controllers()
+        .map_err(CgroupSetupError::Controllers)?;
+    if let Some(missing) = controllers
+        .iter()
+        .find(|controller| !available.iter().any(|a| a == *controller))
+    {
+        return Err(CgroupSetupError::MissingController(missing));
+    }
+    parent
+        .enable_controllers(&controllers)
+        .map_err(CgroupSetupError::Controllers)?;
+    let cgroup =
+        cgroup::Cgroup::create(parent.path().join(name)).map_err(CgroupSetupError::Create)?;
+    if let Err(e) = resources.apply(&cgroup) {
+        if let Err(e) = cgroup.remove() {
+            log::warn!("Failed to remove cgroup: {e}");
+        }
+        return Err(e);
+    }
+    Ok(cgroup)
+}
+
+struct SharedData<'a, 'b, S>
+where
+    S: Step,
+{
+    next: Option<S>,
+    cgroup: &'b cgroup::Cgroup,
+    ctx: &'a mut Context,
+}
+
+fn cgroup_vm<S>(data: &mut SharedData<S>) -> (i32, Result<(), CgroupError<S::Error>>)
+where
+    S: Step,
+{
+    if let Err(e) = data.cgroup.enter() {
+        log::error!("Failed to enter cgroup: {e}");
+        return (1, Err(CgroupError::Enter(e)));
+    }
+    let res = data
+        .next
+        .take()
+        .expect("Component called twice")
+        .run(data.ctx)
+        .map_err(CgroupError::ChildError)
+        .inspect_err(|e| log::error!("{e}"));
+    (0, res)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CgroupSetupError {
+    #[error("Failed to open parent cgroup: {0}")]
+    Open(std::io::Error),
+    #[error("Failed to enable controllers: {0}")]
+    Controllers(std::io::Error),
+    #[error("Controller {0} is not available in the parent cgroup")]
+    MissingController(&'static str),
+    #[error("Failed to create cgroup: {0}")]
+    Create(std::io::Error),
+    #[error("Failed to watch cgroup events: {0}")]
+    Events(std::io::Error),
+    #[error("Failed to set {file}: {error}")]
+    Limit {
+        file: &'static str,
+        error: std::io::Error,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CgroupError<E>
+where
+    E: std::error::Error,
+{
+    #[error(transparent)]
+    Setup(#[from] CgroupSetupError),
+    #[error(transparent)]
+    CloneError(#[from] linux::CloneError),
+    #[error("Failed to enter cgroup: {0}")]
+    Enter(std::io::Error),
+    #[error("Container was killed")]
+    Killed,
+    #[error(transparent)]
+    ChildError(E),
+}
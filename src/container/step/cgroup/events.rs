@@ -0,0 +1,214 @@
+use std::{
+    io::{Read as _, Seek as _},
+    os::fd::AsRawFd as _,
+    time::Duration,
+};
+
+use crate::linux::{self, cgroup};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureResource {
+    Memory,
+    Cpu,
+    Io,
+}
+
+impl PressureResource {
+    fn file(&self) -> &'static str {
+        match self {
+            PressureResource::Memory => "memory.pressure",
+            PressureResource::Cpu => "cpu.pressure",
+            PressureResource::Io => "io.pressure",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressureKind {
+    /// At least one task is stalled.
+    Some,
+    /// All non-idle tasks are stalled.
+    Full,
+}
+
+/// Fires if the tasks of the container were stalled on `resource` for more than `stall` within
+/// `window`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PressureTrigger {
+    pub resource: PressureResource,
+    pub kind: PressureKind,
+    pub stall: Duration,
+    pub window: Duration,
+}
+
+impl PressureTrigger {
+    fn to_trigger(self) -> String {
+        let kind = match self.kind {
+            PressureKind::Some => "some",
+            PressureKind::Full => "full",
+        };
+        format!(
+            "{kind} {} {}",
+            self.stall.as_micros(),
+            self.window.as_micros()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgroupEvent {
+    /// The memory limit was reached. `count` is the total number of times this happened.
+    Oom {
+        count: u64,
+    },
+    /// A process was killed by the OOM killer. `count` is the total number of killed processes.
+    OomKill {
+        count: u64,
+    },
+    Pressure(PressureTrigger),
+}
+
+/// Watches a cgroup for events on a background thread. The thread stops when the watcher is
+/// dropped or the cgroup is removed.
+#[derive(Debug)]
+pub struct EventWatcher {
+    stop: linux::EventFd<usize>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl EventWatcher {
+    pub(super) fn start<F>(
+        cgroup: &cgroup::Cgroup,
+        triggers: &[PressureTrigger],
+        callback: F,
+    ) -> std::io::Result<Self>
+    where
+        F: FnMut(CgroupEvent) + Send + 'static,
+    {
+        let memory_events = match cgroup.open_file("memory.events") {
+            Ok(file) => Some(file),
+            Err(e) => {
+                log::debug!("Not watching memory.events: {e}");
+                None
+            }
+        };
+        let pressure = triggers
+            .iter()
+            .map(|trigger| {
+                cgroup
+                    .open_pressure_trigger(trigger.resource.file(), &trigger.to_trigger())
+                    .map(|file| (*trigger, file))
+            })
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let stop = linux::EventFd::new()?;
+        let watch_stop = stop.clone();
+        let thread = std::thread::Builder::new()
+            .name("cgroup-events".to_string())
+            .spawn(move || watch(watch_stop, memory_events, pressure, callback))?;
+        Ok(Self {
+            stop,
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for EventWatcher {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop.send(1) {
+            log::error!("Failed to stop event watcher: {e}");
+            return;
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn watch<F>(
+    stop: linux::EventFd<usize>,
+    mut memory_events: Option<std::fs::File>,
+    pressure: Vec<(PressureTrigger, std::fs::File)>,
+    mut callback: F,
+) where
+    F: FnMut(CgroupEvent),
+{
+    let mut content = String::new();
+    let mut read_memory_events = |file: &mut std::fs::File| -> std::io::Result<(u64, u64)> {
+        content.clear();
+        file.rewind()?;
+        file.read_to_string(&mut content)?;
+        Ok((
+            cgroup::parse_event(&content, "oom").unwrap_or_default(),
+            cgroup::parse_event(&content, "oom_kill").unwrap_or_default(),
+        ))
+    };
+    let mut last = match memory_events.as_mut().map(&mut read_memory_events) {
+        Some(Ok(counts)) => counts,
+        Some(Err(e)) => {
+            log::error!("Failed to read memory.events: {e}");
+            return;
+        }
+        None => (0, 0),
+    };
+
+    let mut pollfds = vec![libc::pollfd {
+        fd: stop.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    }];
+    pollfds.extend(memory_events.iter().map(|file| libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLPRI,
+        revents: 0,
+    }));
+    let pressure_offset = pollfds.len();
+    pollfds.extend(pressure.iter().map(|(_, file)| libc::pollfd {
+        fd: file.as_raw_fd(),
+        events: libc::POLLPRI,
+        revents: 0,
+    }));
+
+    loop {
+        pollfds.iter_mut().for_each(|pollfd| pollfd.revents = 0);
+        let res = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, -1) };
+        if res == -1 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            log::error!("Failed to poll cgroup events: {err}");
+            return;
+        }
+        if pollfds[0].revents != 0 {
+            log::debug!("Stop watching cgroup events");
+            return;
+        }
+        if let Some(file) = memory_events.as_mut() {
+            if pollfds[1].revents != 0 {
+                let (oom, oom_kill) = match read_memory_events(file) {
+                    Ok(counts) => counts,
+                    Err(e) => {
+                        log::debug!("Cgroup is gone, stop watching events: {e}");
+                        return;
+                    }
+                };
+                if oom > last.0 {
+                    callback(CgroupEvent::Oom { count: oom });
+                }
+                if oom_kill > last.1 {
+                    callback(CgroupEvent::OomKill { count: oom_kill });
+                }
+                last = (oom, oom_kill);
+            }
+        }
+        for ((trigger, _), pollfd) in pressure.iter().zip(&pollfds[pressure_offset..]) {
+            if pollfd.revents & libc::POLLERR != 0 {
+                log::debug!("Pressure file is gone, stop watching events");
+                return;
+            }
+            if pollfd.revents & libc::POLLPRI != 0 {
+                callback(CgroupEvent::Pressure(*trigger));
+            }
+        }
+    }
+}
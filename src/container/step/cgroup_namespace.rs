@@ -0,0 +1,91 @@
+use std::path::{Path, PathBuf};
+
+use nix::mount::MsFlags;
+
+use crate::{
+    container::{step::Step, Context},
+    linux::cgroup::Cgroup,
+};
+
+const CGROUP_MOUNT_POINT: &str = "/sys/fs/cgroup";
+
+/// Unshares the cgroup namespace so the current cgroup becomes the root of the hierarchy seen
+/// by the next steps. If the step runs inside of a mount namespace `cgroup2` is mounted on
+/// `/sys/fs/cgroup`.
+pub struct CgroupNamespace<S>
+where
+    S: Step,
+{
+    next: S,
+    cgroup: Option<PathBuf>,
+}
+
+impl<S> CgroupNamespace<S>
+where
+    S: Step,
+{
+    pub fn new(next: S) -> Self {
+        Self { next, cgroup: None }
+    }
+
+    /// Moves the process into `cgroup` before the namespace is unshared. The cgroup is created
+    /// if it does not exist.
+    pub fn in_cgroup(cgroup: PathBuf, next: S) -> Self {
+        Self {
+            next,
+            cgroup: Some(cgroup),
+        }
+    }
+}
+
+impl<S> Step for CgroupNamespace<S>
+where
+    S: Step,
+{
+    type Error = CgroupNamespaceError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        if let Some(cgroup) = self.cgroup {
+            Cgroup::create(cgroup)
+                .and_then(|cgroup| cgroup.enter())
+                .map_err(CgroupNamespaceError::EnterCgroup)?;
+        }
+        ctx.set_cgroup();
+        log::info!("Unshare cgroup namespace");
+        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWCGROUP)
+            .map_err(CgroupNamespaceError::Unshare)?;
+        if ctx.mnt() {
+            let target = Path::new(CGROUP_MOUNT_POINT);
+            log::debug!("Mount cgroup2 on {target:?}");
+            std::fs::create_dir_all(target).map_err(CgroupNamespaceError::CreateMountPoint)?;
+            nix::mount::mount(
+                Some("cgroup2"),
+                target,
+                Some("cgroup2"),
+                MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+                None::<&str>,
+            )
+            .map_err(CgroupNamespaceError::Mount)?;
+        } else {
+            log::debug!("Not in a mount namespace, skip mounting cgroup2");
+        }
+        self.next.run(ctx).map_err(CgroupNamespaceError::ChildError)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CgroupNamespaceError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Failed to enter cgroup: {0}")]
+    EnterCgroup(std::io::Error),
+    #[error("Failed to unshare {0}")]
+    Unshare(nix::errno::Errno),
+    #[error("Failed to create cgroup mount point: {0}")]
+    CreateMountPoint(std::io::Error),
+    #[error("Failed to mount cgroup2: {0}")]
+    Mount(nix::errno::Errno),
+    #[error(transparent)]
+    ChildError(E),
+}
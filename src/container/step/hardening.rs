@@ -0,0 +1,126 @@
+use crate::{
+    container::{step::Step, Context},
+    linux,
+};
+
+/// Hardens the process running the following steps and the command.
+///
+/// By default `no_new_privs` is set, the process is marked as not dumpable and the command is
+/// killed with `SIGKILL` if its parent exits. The step changes the calling process, so it should
+/// run after a step which created a new process, e.g. a namespace step.
+///
+/// The dumpable flag belongs to the memory, which every step shares with the caller of
+/// [`ContainerBuilder::run`](crate::container::ContainerBuilder::run). It is restored when the
+/// step returns. While the processes are not dumpable, their `/proc` files are owned by root, so
+/// steps like [`UserNamespaceRoot`](super::user_namespace::UserNamespaceRoot) can not be nested.
+/// `execve` resets the flag, so it only protects the processes running the steps.
+pub struct Hardening<S>
+where
+    S: Step,
+{
+    no_new_privs: bool,
+    dumpable: bool,
+    parent_death_signal: Option<libc::c_int>,
+    new_session: bool,
+    new_keyring: bool,
+    next: S,
+}
+
+impl<S> Hardening<S>
+where
+    S: Step,
+{
+    pub fn new(next: S) -> Self {
+        Self {
+            no_new_privs: true,
+            dumpable: false,
+            parent_death_signal: Some(libc::SIGKILL),
+            new_session: false,
+            new_keyring: false,
+            next,
+        }
+    }
+
+    /// Keeps `no_new_privs` unset. Note that Seccomp and Landlock set it anyway.
+    pub fn with_new_privs(mut self) -> Self {
+        self.no_new_privs = false;
+        self
+    }
+
+    pub fn with_dumpable(mut self, dumpable: bool) -> Self {
+        self.dumpable = dumpable;
+        self
+    }
+
+    /// Signal sent to the processes if their parent exits. `None` keeps them running.
+    pub fn with_parent_death_signal(mut self, signal: Option<libc::c_int>) -> Self {
+        self.parent_death_signal = signal;
+        self
+    }
+
+    /// Starts a new session, which detaches the command from the controlling terminal.
+    pub fn with_new_session(mut self) -> Self {
+        self.new_session = true;
+        self
+    }
+
+    /// Replaces the session keyring with a new anonymous keyring, so keys of the caller can not
+    /// be accessed.
+    pub fn with_new_keyring(mut self) -> Self {
+        self.new_keyring = true;
+        self
+    }
+}
+
+impl<S> Step for Hardening<S>
+where
+    S: Step,
+{
+    type Error = HardeningError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        if let Some(signal) = self.parent_death_signal {
+            log::trace!("Set parent death signal to {signal}");
+            linux::set_parent_death_signal(signal).map_err(HardeningError::ParentDeathSignal)?;
+            ctx.set_parent_death_signal(signal);
+        }
+        if self.no_new_privs {
+            log::trace!("Set no_new_privs");
+            linux::set_no_new_privs().map_err(HardeningError::NoNewPrivs)?;
+        }
+        if self.new_session {
+            log::trace!("Start new session");
+            linux::new_session().map_err(HardeningError::Session)?;
+        }
+        if self.new_keyring {
+            log::trace!("Join new session keyring");
+            linux::join_new_session_keyring().map_err(HardeningError::Keyring)?;
+        }
+        let dumpable = linux::get_dumpable().map_err(HardeningError::Dumpable)?;
+        log::trace!("Set dumpable to {}", self.dumpable);
+        linux::set_dumpable(self.dumpable).map_err(HardeningError::Dumpable)?;
+        let res = self.next.run(ctx).map_err(HardeningError::ChildError);
+        log::trace!("Restore dumpable to {dumpable}");
+        let restored = linux::set_dumpable(dumpable).map_err(HardeningError::Dumpable);
+        res.and(restored)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HardeningError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Failed to set no_new_privs: {0}")]
+    NoNewPrivs(std::io::Error),
+    #[error("Failed to set dumpable: {0}")]
+    Dumpable(std::io::Error),
+    #[error("Failed to set parent death signal: {0}")]
+    ParentDeathSignal(std::io::Error),
+    #[error("Failed to start new session: {0}")]
+    Session(std::io::Error),
+    #[error("Failed to join new session keyring: {0}")]
+    Keyring(std::io::Error),
+    #[error(transparent)]
+    ChildError(E),
+}
@@ -0,0 +1,92 @@
+use std::path::Path;
+
+use nix::mount::MsFlags;
+
+use crate::container::{step::Step, Context};
+
+const DEFAULT_SHM_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Unshares the IPC namespace. If the step runs inside of a mount namespace a new `mqueue`
+/// filesystem is mounted on `/dev/mqueue` and a size limited tmpfs on `/dev/shm`.
+pub struct IpcNamespace<S>
+where
+    S: Step,
+{
+    next: S,
+    shm_size: u64,
+}
+
+impl<S> IpcNamespace<S>
+where
+    S: Step,
+{
+    pub fn new(next: S) -> Self {
+        Self::with_shm_size(DEFAULT_SHM_SIZE, next)
+    }
+
+    pub fn with_shm_size(shm_size: u64, next: S) -> Self {
+        Self { next, shm_size }
+    }
+}
+
+impl<S> Step for IpcNamespace<S>
+where
+    S: Step,
+{
+    type Error = IpcNamespaceError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        ctx.set_ipc();
+        log::info!("Unshare ipc namespace");
+        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWIPC)
+            .map_err(IpcNamespaceError::Unshare)?;
+        if ctx.mnt() {
+            let flags = MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC;
+            mount_fs(Path::new("/dev/mqueue"), "mqueue", flags, None)?;
+            let options = format!("mode=1777,size={}", self.shm_size);
+            mount_fs(Path::new("/dev/shm"), "tmpfs", flags, Some(&options))?;
+        } else {
+            log::debug!("Not in a mount namespace, skip mounting mqueue and shm");
+        }
+        self.next.run(ctx).map_err(IpcNamespaceError::ChildError)
+    }
+}
+
+fn mount_fs<E>(
+    target: &Path,
+    fs_type: &'static str,
+    flags: MsFlags,
+    data: Option<&str>,
+) -> Result<(), IpcNamespaceError<E>>
+where
+    E: std::error::Error,
+{
+    log::debug!("Mount {fs_type} on {target:?} with options {data:?}");
+    std::fs::create_dir_all(target).map_err(|error| IpcNamespaceError::CreateMountPoint {
+        target: target.to_path_buf(),
+        error,
+    })?;
+    nix::mount::mount(Some(fs_type), target, Some(fs_type), flags, data)
+        .map_err(|error| IpcNamespaceError::Mount { fs_type, error })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum IpcNamespaceError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Failed to unshare {0}")]
+    Unshare(nix::errno::Errno),
+    #[error("Failed to create mount point {target:?}: {error}")]
+    CreateMountPoint {
+        target: std::path::PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Failed to mount {fs_type}: {error}")]
+    Mount {
+        fs_type: &'static str,
+        error: nix::errno::Errno,
+    },
+    #[error(transparent)]
+    ChildError(E),
+}
@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+
+use crate::{
+    container::{step::Step, Context},
+    linux::landlock::{self, Ruleset},
+};
+
+const READ_ONLY: u64 = landlock::ACCESS_FS_READ_FILE | landlock::ACCESS_FS_READ_DIR;
+const EXECUTE: u64 = landlock::ACCESS_FS_EXECUTE | landlock::ACCESS_FS_READ_FILE;
+const READ_WRITE: u64 = u64::MAX;
+
+/// Restricts the file system and network access of the command started by
+/// [`RunCommand`](super::run_command::RunCommand) with Landlock. Everything which is not allowed
+/// by one of the rules is denied.
+///
+/// Rights which are unknown to the running kernel can not be restricted. Unless
+/// [`Self::required`] is set, the command is run without Landlock on kernels which do not
+/// support it and without network rules on kernels older than ABI 4. The ruleset is enforced
+/// right before the command is executed and the command is run with `no_new_privs` set.
+pub struct Landlock<S>
+where
+    S: Step,
+{
+    read_only: Vec<PathBuf>,
+    read_write: Vec<PathBuf>,
+    execute: Vec<PathBuf>,
+    tcp_bind: Option<Vec<u16>>,
+    tcp_connect: Option<Vec<u16>>,
+    required: bool,
+    next: S,
+}
+
+impl<S> Landlock<S>
+where
+    S: Step,
+{
+    pub fn new(next: S) -> Self {
+        Self {
+            read_only: Vec::new(),
+            read_write: Vec::new(),
+            execute: Vec::new(),
+            tcp_bind: None,
+            tcp_connect: None,
+            required: false,
+            next,
+        }
+    }
+
+    /// Allows reading files and listing directories beneath `path`.
+    pub fn with_read_only(mut self, path: PathBuf) -> Self {
+        self.read_only.push(path);
+        self
+    }
+
+    /// Allows every file system access beneath `path`.
+    pub fn with_read_write(mut self, path: PathBuf) -> Self {
+        self.read_write.push(path);
+        self
+    }
+
+    /// Allows executing files beneath `path`. The kernel opens executables for reading, so
+    /// files beneath `path` can be read as well, but directories can not be listed.
+    pub fn with_execute(mut self, path: PathBuf) -> Self {
+        self.execute.push(path);
+        self
+    }
+
+    /// Only allows binding TCP sockets to `ports`. Requires ABI 4.
+    pub fn with_tcp_bind(mut self, ports: Vec<u16>) -> Self {
+        self.tcp_bind = Some(ports);
+        self
+    }
+
+    /// Only allows connecting TCP sockets to `ports`. Requires ABI 4.
+    pub fn with_tcp_connect(mut self, ports: Vec<u16>) -> Self {
+        self.tcp_connect = Some(ports);
+        self
+    }
+
+    /// Fails instead of running the command with fewer restrictions, if the kernel does not
+    /// support Landlock or the network rules.
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    fn build<E>(&self, abi: u32) -> Result<Ruleset, LandlockError<E>>
+    where
+        E: std::error::Error,
+    {
+        let fs_access = landlock::supported_fs_access(abi);
+        let mut net_access = 0;
+        if self.tcp_bind.is_some() {
+            net_access |= landlock::ACCESS_NET_BIND_TCP;
+        }
+        if self.tcp_connect.is_some() {
+            net_access |= landlock::ACCESS_NET_CONNECT_TCP;
+        }
+        if net_access & !landlock::supported_net_access(abi) != 0 {
+            if self.required {
+                return Err(LandlockError::NetworkUnsupported(abi));
+            }
+            log::warn!("Landlock ABI {abi} does not support network rules, ignore them");
+            net_access = 0;
+        }
+
+        let ruleset = Ruleset::new(fs_access, net_access).map_err(LandlockError::Ruleset)?;
+        let paths = [
+            (&self.read_only, READ_ONLY),
+            (&self.read_write, READ_WRITE),
+            (&self.execute, EXECUTE),
+        ];
+        for (paths, access) in paths {
+            for path in paths {
+                let mut access = access & fs_access;
+                if !path.is_dir() {
+                    access &= landlock::ACCESS_FS_FILE;
+                }
+                log::debug!("Allow {access:#x} beneath {path:?}");
+                ruleset
+                    .allow_path(path, access)
+                    .map_err(|error| LandlockError::Path {
+                        path: path.clone(),
+                        error,
+                    })?;
+            }
+        }
+        if net_access == 0 {
+            return Ok(ruleset);
+        }
+        let ports = [
+            (&self.tcp_bind, landlock::ACCESS_NET_BIND_TCP),
+            (&self.tcp_connect, landlock::ACCESS_NET_CONNECT_TCP),
+        ];
+        for (ports, access) in ports {
+            for port in ports.iter().flatten() {
+                log::debug!("Allow {access:#x} on port {port}");
+                ruleset
+                    .allow_port(*port, access)
+                    .map_err(|error| LandlockError::Port { port: *port, error })?;
+            }
+        }
+        Ok(ruleset)
+    }
+}
+
+impl<S> Step for Landlock<S>
+where
+    S: Step,
+{
+    type Error = LandlockError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        log::trace!("Create landlock ruleset");
+        let abi = landlock::abi_version();
+        if abi == 0 {
+            if self.required {
+                return Err(LandlockError::Unsupported);
+            }
+            log::warn!("Landlock is not supported, the command is not restricted");
+        } else {
+            log::debug!("Landlock ABI version {abi}");
+            ctx.add_landlock_ruleset(self.build(abi)?);
+        }
+        self.next.run(ctx).map_err(LandlockError::ChildError)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LandlockError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Landlock is not supported by the kernel")]
+    Unsupported,
+    #[error("Landlock ABI {0} does not support network rules")]
+    NetworkUnsupported(u32),
+    #[error("Failed to create landlock ruleset: {0}")]
+    Ruleset(std::io::Error),
+    #[error("Failed to add landlock rule for {path:?}: {error}")]
+    Path {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    #[error("Failed to add landlock rule for port {port}: {error}")]
+    Port { port: u16, error: std::io::Error },
+    #[error(transparent)]
+    ChildError(E),
+}
@@ -17,6 +17,7 @@ where
 {
     next: C,
     operations: Vec<MountOperation<'a>>,
+    root_propagation: Propagation,
 }
 
 impl<'a, C> MountNamespace<'a, C>
@@ -27,8 +28,16 @@ where
         Self {
             next: c,
             operations,
+            root_propagation: Propagation::Private,
         }
     }
+
+    /// Propagation set recursively on all mounts after unsharing the namespace. Defaults to
+    /// [`Propagation::Private`], so no mounts propagate between the host and the container.
+    pub fn with_root_propagation(mut self, propagation: Propagation) -> Self {
+        self.root_propagation = propagation;
+        self
+    }
 }
 
 impl<'a, C> Step for MountNamespace<'a, C>
@@ -42,9 +51,12 @@ where
         log::info!("Unshare mount namespace");
         nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNS)
             .map_err(MountNamespaceError::Unshare)?;
+        log::debug!("Set root propagation to {:?}", self.root_propagation);
+        set_propagation(std::path::Path::new("/"), self.root_propagation, true)
+            .map_err(MountNamespaceError::RootPropagation)?;
         self.operations
             .into_iter()
-            .try_for_each(MountOperation::run)?;
+            .try_for_each(|operation| operation.run_with_context(ctx))?;
         log::info!("Finished mounting");
         self.next.run(ctx).map_err(MountNamespaceError::ChildError)
     }
@@ -57,6 +69,8 @@ where
 {
     #[error("Failed to unshare {0}")]
     Unshare(nix::errno::Errno),
+    #[error("Failed to set propagation of root {0}")]
+    RootPropagation(MountingError),
     #[error("Failed to run mount operation {0}")]
     Op(#[from] MountingError),
     #[error(transparent)]
@@ -76,9 +90,16 @@ pub enum MountOperation<'a> {
         auto_unmount: bool,
         create_if_does_not_exisit: bool,
     },
+    /// Binds `src` to `target`. Submounts of `src` are only bound if `recursive` is set.
+    ///
+    /// `flags` can contain `MS_RDONLY`, `MS_NOSUID`, `MS_NODEV`, `MS_NOEXEC` and the atime flags.
+    /// They are applied by remounting the bind and only affect the top mount, not the bound
+    /// submounts.
     BindMount {
         src: Option<PathBuf>,
         target: PathBuf,
+        recursive: bool,
+        flags: MsFlags,
     },
     Unmount {
         mount: PathBuf,
@@ -91,18 +112,150 @@ pub enum MountOperation<'a> {
         flags: nix::mount::MsFlags,
         data: Option<&'a std::ffi::CStr>,
     },
+    CreateDir {
+        path: PathBuf,
+    },
+    /// Creates an empty file, e.g. as target for binding a file.
+    CreateFile {
+        path: PathBuf,
+    },
+    Symlink {
+        original: PathBuf,
+        link: PathBuf,
+    },
+    /// Hides `path` by mounting `/dev/null` or an empty read-only tmpfs over it. Paths which do
+    /// not exist are ignored.
+    MaskPath {
+        path: PathBuf,
+    },
+    /// Makes `path` read-only by binding it to itself. Paths which do not exist are ignored.
+    ReadOnlyPath {
+        path: PathBuf,
+    },
+    /// Changes the propagation of the mount at `target` and of its submounts if `recursive` is
+    /// set.
+    SetPropagation {
+        target: PathBuf,
+        propagation: Propagation,
+        recursive: bool,
+    },
+    /// Attaches the idmapped bind of `src` prepared by
+    /// [`UserNamespaceRoot::with_idmapped_mount`](super::user_namespace::UserNamespaceRoot::with_idmapped_mount)
+    /// to `target`.
+    IdmappedBindMount {
+        src: PathBuf,
+        target: PathBuf,
+    },
+    /// Mounts a file system with `fsopen` and `fsmount`. Errors contain the messages logged by
+    /// the kernel. `options` are passed as flags or strings to `fsconfig`.
+    ///
+    /// `flags` can contain `MS_RDONLY`, `MS_NOSUID`, `MS_NODEV`, `MS_NOEXEC`, `MS_NOATIME`,
+    /// `MS_STRICTATIME` and `MS_NODIRATIME`.
+    NewMount {
+        fs_type: String,
+        source: Option<String>,
+        target: PathBuf,
+        options: Vec<(String, Option<String>)>,
+        flags: MsFlags,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Propagation {
+    /// Mount events are not propagated in either direction.
+    Private,
+    /// Mount events propagate from the peer group into the mount, but not back.
+    Slave,
+    /// Mount events propagate in both directions.
+    Shared,
+    /// Like [`Self::Private`], but the mount can not be bound.
+    Unbindable,
 }
 
+impl Propagation {
+    fn flags(self) -> MsFlags {
+        match self {
+            Self::Private => MsFlags::MS_PRIVATE,
+            Self::Slave => MsFlags::MS_SLAVE,
+            Self::Shared => MsFlags::MS_SHARED,
+            Self::Unbindable => MsFlags::MS_UNBINDABLE,
+        }
+    }
+}
+
+/// Paths masked by [`MountOperation::restricted_paths`].
+pub const DEFAULT_MASKED_PATHS: &[&str] = &[
+    "/proc/acpi",
+    "/proc/asound",
+    "/proc/interrupts",
+    "/proc/kcore",
+    "/proc/keys",
+    "/proc/latency_stats",
+    "/proc/sched_debug",
+    "/proc/scsi",
+    "/proc/timer_list",
+    "/proc/timer_stats",
+    "/sys/devices/virtual/powercap",
+    "/sys/firmware",
+];
+/// Paths made read-only by [`MountOperation::restricted_paths`].
+pub const DEFAULT_READ_ONLY_PATHS: &[&str] = &[
+    "/proc/bus",
+    "/proc/fs",
+    "/proc/irq",
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+];
+
+/// Devices bound from the host into the tmpfs mounted on `/dev`.
+const DEVICES: &[&str] = &["null", "zero", "full", "random", "urandom", "tty"];
+const DEV_SYMLINKS: &[(&str, &str)] = &[
+    ("/proc/self/fd", "fd"),
+    ("/proc/self/fd/0", "stdin"),
+    ("/proc/self/fd/1", "stdout"),
+    ("/proc/self/fd/2", "stderr"),
+    ("pts/ptmx", "ptmx"),
+];
+
 impl<'a> MountOperation<'a> {
+    /// Binds `src` to `target` without submounts. Use [`Self::recursive`], [`Self::read_only`]
+    /// and [`Self::with_flags`] to change the bind.
+    pub fn bind(src: impl Into<PathBuf>, target: impl Into<PathBuf>) -> Self {
+        Self::BindMount {
+            src: Some(src.into()),
+            target: target.into(),
+            recursive: false,
+            flags: MsFlags::empty(),
+        }
+    }
+
+    /// Also binds the submounts of `src`. Only changes [`Self::BindMount`].
+    pub fn recursive(mut self) -> Self {
+        if let Self::BindMount { recursive, .. } = &mut self {
+            *recursive = true;
+        }
+        self
+    }
+
+    /// Makes the bind read-only. Only changes [`Self::BindMount`].
+    pub fn read_only(self) -> Self {
+        self.with_flags(MsFlags::MS_RDONLY)
+    }
+
+    /// Adds `flags` to the flags of a [`Self::BindMount`].
+    pub fn with_flags(mut self, new_flags: MsFlags) -> Self {
+        if let Self::BindMount { flags, .. } = &mut self {
+            flags.insert(new_flags);
+        }
+        self
+    }
+
     pub fn switch_root(
         new_root: impl Into<PathBuf> + Clone,
         put_old: impl Into<PathBuf> + Clone,
     ) -> Vec<Self> {
         vec![
-            Self::BindMount {
-                src: Some(new_root.clone().into()),
-                target: new_root.clone().into(),
-            },
+            Self::bind(new_root.clone(), new_root.clone()),
             Self::PivotRoot {
                 new_root: new_root.into(),
                 put_old: put_old.into(),
@@ -118,42 +271,148 @@ impl<'a> MountOperation<'a> {
         work_sys: impl Into<PathBuf> + Clone,
         new_root: impl Into<PathBuf> + Clone,
         put_old: impl Into<PathBuf> + Clone,
-        //mount_sys_dirs: bool,
     ) -> Vec<Self> {
         let new_root: PathBuf = new_root.into();
-        let upper_rw: PathBuf = upper_rw.into();
+        let mut operations = Self::overlay_root(lower_ro, upper_rw, work_sys, new_root.clone());
+        operations.push(Self::overlay_pivot_root(new_root, put_old));
+        operations
+    }
+
+    /// Like [`Self::switch_root_with_overlay`], but mounts [`Self::pseudo_filesystems`] into the
+    /// new root before pivoting.
+    pub fn switch_root_with_overlay_and_pseudo_fs(
+        lower_ro: impl Into<PathBuf> + Clone,
+        upper_rw: impl Into<PathBuf> + Clone,
+        work_sys: impl Into<PathBuf> + Clone,
+        new_root: impl Into<PathBuf> + Clone,
+        put_old: impl Into<PathBuf> + Clone,
+    ) -> Vec<Self> {
+        let new_root: PathBuf = new_root.into();
+        let mut operations = Self::overlay_root(lower_ro, upper_rw, work_sys, new_root.clone());
+        operations.extend(Self::pseudo_filesystems(new_root.clone()));
+        operations.push(Self::overlay_pivot_root(new_root, put_old));
+        operations
+    }
+
+    fn overlay_root(
+        lower_ro: impl Into<PathBuf>,
+        upper_rw: impl Into<PathBuf>,
+        work_sys: impl Into<PathBuf>,
+        new_root: PathBuf,
+    ) -> Vec<Self> {
         vec![
             Self::OverlayMount {
                 lower: lower_ro.into(),
-                upper: upper_rw.clone().into(),
+                upper: upper_rw.into(),
                 work: work_sys.into(),
                 merged: new_root.clone(),
             },
-            /*Self::Mount {
-                source: "devpts".into(),
-                target: new_root.join("dev/pts"),
-                fs_type: Some(c"devpts"),
-                flags: 0,
-                data: None,
+            Self::bind(new_root.clone(), new_root),
+        ]
+    }
+
+    fn overlay_pivot_root(new_root: PathBuf, put_old: impl Into<PathBuf>) -> Self {
+        Self::PivotRoot {
+            new_root,
+            put_old: put_old.into(),
+            auto_unmount: false,
+            create_if_does_not_exisit: false,
+        }
+    }
+
+    /// Mounts `/proc`, a read-only `/sys` and a minimal `/dev` below `new_root`. Must run before
+    /// [`Self::PivotRoot`], because the devices are bound from the host.
+    ///
+    /// `proc` can only be mounted in a new PID namespace and `sysfs` only in a new network
+    /// namespace, if the mount namespace is owned by a user namespace.
+    pub fn pseudo_filesystems(new_root: impl Into<PathBuf>) -> Vec<Self> {
+        let new_root: PathBuf = new_root.into();
+        let dev = new_root.join("dev");
+        let mut operations = vec![
+            Self::CreateDir {
+                path: new_root.join("proc"),
             },
             Self::Mount {
-                source: None,
+                source: Some("proc".into()),
                 target: new_root.join("proc"),
                 fs_type: Some(c"proc"),
-                flags: MsFlags::empty(),
+                flags: MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
                 data: None,
-            },*/
-            Self::BindMount {
-                src: new_root.clone().into(),
-                target: new_root.clone().into(),
             },
-            Self::PivotRoot {
-                new_root: new_root.into(),
-                put_old: put_old.into(),
-                auto_unmount: false,
-                create_if_does_not_exisit: false,
+            Self::CreateDir {
+                path: new_root.join("sys"),
             },
-        ]
+            Self::Mount {
+                source: Some("sysfs".into()),
+                target: new_root.join("sys"),
+                fs_type: Some(c"sysfs"),
+                flags: MsFlags::MS_RDONLY
+                    | MsFlags::MS_NOSUID
+                    | MsFlags::MS_NODEV
+                    | MsFlags::MS_NOEXEC,
+                data: None,
+            },
+            Self::CreateDir { path: dev.clone() },
+            Self::Mount {
+                source: Some("tmpfs".into()),
+                target: dev.clone(),
+                fs_type: Some(c"tmpfs"),
+                flags: MsFlags::MS_NOSUID | MsFlags::MS_STRICTATIME,
+                data: Some(c"mode=755,size=65536k"),
+            },
+        ];
+        for device in DEVICES {
+            operations.push(Self::CreateFile {
+                path: dev.join(device),
+            });
+            operations.push(Self::bind(
+                PathBuf::from("/dev").join(device),
+                dev.join(device),
+            ));
+        }
+        operations.push(Self::CreateDir {
+            path: dev.join("pts"),
+        });
+        operations.push(Self::Mount {
+            source: Some("devpts".into()),
+            target: dev.join("pts"),
+            fs_type: Some(c"devpts"),
+            flags: MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC,
+            data: Some(c"newinstance,ptmxmode=0666,mode=0620"),
+        });
+        operations.extend(DEV_SYMLINKS.iter().map(|(original, link)| Self::Symlink {
+            original: original.into(),
+            link: dev.join(link),
+        }));
+        operations
+    }
+
+    /// Masks [`DEFAULT_MASKED_PATHS`] and makes [`DEFAULT_READ_ONLY_PATHS`] read-only. Must run
+    /// after [`Self::PivotRoot`] and after `/proc`, `/sys` and `/dev/null` are mounted, e.g. by
+    /// [`Self::pseudo_filesystems`].
+    pub fn restricted_paths() -> Vec<Self> {
+        DEFAULT_MASKED_PATHS
+            .iter()
+            .map(|path| Self::MaskPath { path: path.into() })
+            .chain(
+                DEFAULT_READ_ONLY_PATHS
+                    .iter()
+                    .map(|path| Self::ReadOnlyPath { path: path.into() }),
+            )
+            .collect()
+    }
+
+    fn run_with_context(self, ctx: &mut Context) -> Result<(), MountingError> {
+        match self {
+            MountOperation::IdmappedBindMount { src, target } => {
+                log::debug!("Attach idmapped mount of {src:?} to {target:?}");
+                ctx.take_idmapped_mount(&src)
+                    .ok_or(MountingError::IdmappedMountNotPrepared(src))?
+                    .attach(&target)
+                    .map_err(MountingError::MountApi)
+            }
+            operation => operation.run(),
+        }
     }
 
     fn run(self) -> Result<(), MountingError> {
@@ -193,19 +452,33 @@ impl<'a> MountOperation<'a> {
                 auto_unmount,
                 create_if_does_not_exisit,
             ),
-            MountOperation::BindMount { src, target } => {
-                log::debug!("Bind {:?} to {:?}", src, target);
+            MountOperation::BindMount {
+                src,
+                target,
+                recursive,
+                flags,
+            } => {
+                log::debug!("Bind {src:?} to {target:?} recursive: {recursive} flags: {flags:?}");
+                let bind_flags = if recursive {
+                    MsFlags::MS_BIND | MsFlags::MS_REC
+                } else {
+                    MsFlags::MS_BIND
+                };
                 nix::mount::mount(
                     src.as_ref(),
                     &target,
                     None::<&CStr>,
-                    MsFlags::MS_BIND,
+                    bind_flags,
                     None::<&CStr>,
                 )
                 .map_err(|error| MountingError::Fallback {
                     mount_type: "bind",
                     error,
-                })
+                })?;
+                if flags.is_empty() {
+                    return Ok(());
+                }
+                remount_bind(&target, flags)
             }
             MountOperation::Unmount { mount, lazy } => {
                 log::debug!("Unmount {} lazy: {lazy}", mount.to_string_lossy());
@@ -234,6 +507,51 @@ impl<'a> MountOperation<'a> {
                     }
                 })
             }
+            MountOperation::CreateDir { path } => {
+                log::debug!("Create directory {path:?}");
+                std::fs::create_dir_all(&path)
+                    .map_err(|error| MountingError::Create { path, error })
+            }
+            MountOperation::CreateFile { path } => {
+                log::debug!("Create file {path:?}");
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .truncate(false)
+                    .write(true)
+                    .open(&path)
+                    .map(|_| ())
+                    .map_err(|error| MountingError::Create { path, error })
+            }
+            MountOperation::Symlink { original, link } => {
+                log::debug!("Link {link:?} to {original:?}");
+                std::os::unix::fs::symlink(original, &link)
+                    .map_err(|error| MountingError::Create { path: link, error })
+            }
+            MountOperation::MaskPath { path } => mask_path(&path),
+            MountOperation::ReadOnlyPath { path } => read_only_path(&path),
+            MountOperation::SetPropagation {
+                target,
+                propagation,
+                recursive,
+            } => {
+                log::debug!(
+                    "Set propagation of {target:?} to {propagation:?} recursive: {recursive}"
+                );
+                set_propagation(&target, propagation, recursive)
+            }
+            MountOperation::IdmappedBindMount { src, .. } => {
+                Err(MountingError::IdmappedMountNotPrepared(src))
+            }
+            MountOperation::NewMount {
+                fs_type,
+                source,
+                target,
+                options,
+                flags,
+            } => {
+                log::debug!("Mount {source:?} of type {fs_type} to {target:?} with flags: {flags:?} and options {options:?}");
+                new_mount(&fs_type, source, &target, options, flags)
+            }
         }
     }
 }
@@ -271,6 +589,144 @@ fn pivot_root(
     Ok(())
 }
 
+fn new_mount(
+    fs_type: &str,
+    source: Option<String>,
+    target: &std::path::Path,
+    options: Vec<(String, Option<String>)>,
+    flags: MsFlags,
+) -> Result<(), MountingError> {
+    use linux::mount_api;
+    let fs = mount_api::FsContext::new(fs_type)?;
+    if let Some(source) = source {
+        fs.set_string("source", source.as_bytes())?;
+    }
+    for (key, value) in options {
+        match value {
+            Some(value) => fs.set_string(&key, value.as_bytes())?,
+            None => fs.set_flag(&key)?,
+        }
+    }
+    let attributes = [
+        (MsFlags::MS_RDONLY, mount_api::MOUNT_ATTR_RDONLY),
+        (MsFlags::MS_NOSUID, mount_api::MOUNT_ATTR_NOSUID),
+        (MsFlags::MS_NODEV, mount_api::MOUNT_ATTR_NODEV),
+        (MsFlags::MS_NOEXEC, mount_api::MOUNT_ATTR_NOEXEC),
+        (MsFlags::MS_NOATIME, mount_api::MOUNT_ATTR_NOATIME),
+        (MsFlags::MS_STRICTATIME, mount_api::MOUNT_ATTR_STRICTATIME),
+        (MsFlags::MS_NODIRATIME, mount_api::MOUNT_ATTR_NODIRATIME),
+    ]
+    .into_iter()
+    .filter(|(flag, _)| flags.contains(*flag))
+    .fold(0, |attributes, (_, attribute)| attributes | attribute);
+    fs.mount(attributes)?.attach(target)?;
+    Ok(())
+}
+
+fn set_propagation(
+    target: &std::path::Path,
+    propagation: Propagation,
+    recursive: bool,
+) -> Result<(), MountingError> {
+    let flags = if recursive {
+        propagation.flags() | MsFlags::MS_REC
+    } else {
+        propagation.flags()
+    };
+    nix::mount::mount(None::<&CStr>, target, None::<&CStr>, flags, None::<&CStr>).map_err(|error| {
+        MountingError::Fallback {
+            mount_type: "propagation",
+            error,
+        }
+    })
+}
+
+fn mask_path(path: &std::path::Path) -> Result<(), MountingError> {
+    let Ok(metadata) = path.metadata() else {
+        log::debug!("Skip masking {path:?}, it does not exist");
+        return Ok(());
+    };
+    log::debug!("Mask {path:?}");
+    if metadata.is_dir() {
+        nix::mount::mount(
+            Some("tmpfs"),
+            path,
+            Some("tmpfs"),
+            MsFlags::MS_RDONLY,
+            None::<&CStr>,
+        )
+    } else {
+        nix::mount::mount(
+            Some("/dev/null"),
+            path,
+            None::<&CStr>,
+            MsFlags::MS_BIND,
+            None::<&CStr>,
+        )
+    }
+    .map_err(|error| MountingError::Fallback {
+        mount_type: "mask",
+        error,
+    })
+}
+
+fn read_only_path(path: &std::path::Path) -> Result<(), MountingError> {
+    if !path.exists() {
+        log::debug!("Skip making {path:?} read-only, it does not exist");
+        return Ok(());
+    }
+    log::debug!("Make {path:?} read-only");
+    let error = |error| MountingError::Fallback {
+        mount_type: "read-only",
+        error,
+    };
+    nix::mount::mount(
+        Some(path),
+        path,
+        None::<&CStr>,
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        None::<&CStr>,
+    )
+    .map_err(error)?;
+    remount_bind(path, MsFlags::MS_RDONLY)
+}
+
+/// Changes the flags of the bind mount at `path`.
+fn remount_bind(path: &std::path::Path, flags: MsFlags) -> Result<(), MountingError> {
+    let error = |error| MountingError::Fallback {
+        mount_type: "remount",
+        error,
+    };
+    // Flags of the mount which are locked in a user namespace must be kept on remount.
+    let current = nix::sys::statvfs::statvfs(path).map_err(error)?.flags();
+    let kept = [
+        (nix::sys::statvfs::FsFlags::ST_RDONLY, MsFlags::MS_RDONLY),
+        (nix::sys::statvfs::FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+        (nix::sys::statvfs::FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+        (nix::sys::statvfs::FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+        (nix::sys::statvfs::FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+        (
+            nix::sys::statvfs::FsFlags::ST_NODIRATIME,
+            MsFlags::MS_NODIRATIME,
+        ),
+        (
+            nix::sys::statvfs::FsFlags::ST_RELATIME,
+            MsFlags::MS_RELATIME,
+        ),
+    ]
+    .into_iter()
+    .filter(|(st, _)| current.contains(*st))
+    .fold(MsFlags::empty(), |kept, (_, ms)| kept | ms);
+    nix::mount::mount(
+        None::<&CStr>,
+        path,
+        None::<&CStr>,
+        MsFlags::MS_BIND | MsFlags::MS_REMOUNT | flags | kept,
+        None::<&CStr>,
+    )
+    .map_err(error)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum MountingError {
     #[error("Mount operation failed type: \"{mount_type}\" error: {error}")]
@@ -284,4 +740,13 @@ pub enum MountingError {
     UnableToCreatePutOld(std::io::Error),
     #[error("Failed to remove put_old")]
     UnableToRmPutOld(std::io::Error),
+    #[error(transparent)]
+    MountApi(#[from] linux::mount_api::MountApiError),
+    #[error("No idmapped mount of {0:?} was prepared by UserNamespaceRoot")]
+    IdmappedMountNotPrepared(PathBuf),
+    #[error("Failed to create {path:?}: {error}")]
+    Create {
+        path: PathBuf,
+        error: std::io::Error,
+    },
 }
@@ -0,0 +1,68 @@
+use crate::{
+    container::{step::Step, Context},
+    linux,
+};
+
+pub struct NetNamespace<S>
+where
+    S: Step,
+{
+    next: S,
+    loopback_up: bool,
+}
+
+impl<S> NetNamespace<S>
+where
+    S: Step,
+{
+    pub fn new(next: S) -> Self {
+        Self {
+            next,
+            loopback_up: true,
+        }
+    }
+
+    pub fn without_loopback(next: S) -> Self {
+        Self {
+            next,
+            loopback_up: false,
+        }
+    }
+}
+
+impl<S> Step for NetNamespace<S>
+where
+    S: Step,
+{
+    type Error = NetNamespaceError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        ctx.set_net();
+        log::info!("Unshare network namespace");
+        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNET)
+            .map_err(NetNamespaceError::Unshare)?;
+        if self.loopback_up {
+            loopback_up().map_err(NetNamespaceError::Loopback)?;
+        }
+        self.next.run(ctx).map_err(NetNamespaceError::ChildError)
+    }
+}
+
+pub(crate) fn loopback_up() -> std::io::Result<()> {
+    let mut netlink = linux::netlink::Netlink::new()?;
+    let lo = netlink.link_index("lo")?;
+    netlink.set_link_up(lo)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NetNamespaceError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Failed to unshare {0}")]
+    Unshare(nix::errno::Errno),
+    #[error("Failed to bring up loopback device: {0}")]
+    Loopback(std::io::Error),
+    #[error(transparent)]
+    ChildError(E),
+}
@@ -1,18 +1,117 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use crate::{
     container::{step::Step, Context},
     linux,
 };
 
-pub struct PIDNamespace<S>(S)
+pub struct PIDNamespace<S>
 where
-    S: Step;
+    S: Step,
+{
+    next: S,
+    handle: PidNamespaceHandle,
+}
 
 impl<S> PIDNamespace<S>
 where
     S: Step,
 {
     pub fn new(next: S) -> Self {
-        Self(next)
+        Self {
+            next,
+            handle: PidNamespaceHandle::default(),
+        }
+    }
+
+    /// Returns a handle that can be used to stop the processes of the namespace while it is
+    /// running, e.g. from another thread.
+    pub fn handle(&self) -> PidNamespaceHandle {
+        self.handle.clone()
+    }
+}
+
+/// Signals the processes of a running [`PIDNamespace`]. The processes are found by their PID
+/// namespace, so this works without a [`Cgroup`](super::cgroup::Cgroup) and also reaches
+/// daemons which left the process tree of the container.
+#[derive(Debug, Clone, Default)]
+pub struct PidNamespaceHandle {
+    init: Arc<Mutex<Option<Init>>>,
+}
+
+#[derive(Debug)]
+struct Init {
+    pid: libc::pid_t,
+    pidfd: std::fs::File,
+}
+
+impl PidNamespaceHandle {
+    fn set_init(&self, pid: libc::pid_t) {
+        match linux::pidfd_open(pid as u32) {
+            Ok(pidfd) => *self.init.lock().unwrap() = Some(Init { pid, pidfd }),
+            Err(e) => log::warn!("Failed to open pidfd of PID namespace init {pid}: {e}"),
+        }
+    }
+
+    fn clear_init(&self) {
+        *self.init.lock().unwrap() = None;
+    }
+
+    /// The init process of the namespace if it is still running. Signals are sent to init through
+    /// its pidfd, so they can not reach a process which reused its pid.
+    fn init(&self) -> std::io::Result<Option<Init>> {
+        let init = self.init.lock().unwrap();
+        let Some(init) = init.as_ref() else {
+            return Ok(None);
+        };
+        if linux::wait_exit(&init.pidfd, Duration::ZERO)? {
+            return Ok(None);
+        }
+        Ok(Some(Init {
+            pid: init.pid,
+            pidfd: init.pidfd.try_clone()?,
+        }))
+    }
+
+    /// Sends `signal` to every process in the namespace. Does nothing if the namespace is not
+    /// running.
+    ///
+    /// The init process of the namespace ignores all signals except `SIGKILL`, which kills every
+    /// process of the namespace.
+    pub fn kill_all(&self, signal: libc::c_int) -> std::io::Result<()> {
+        let Some(init) = self.init()? else {
+            return Ok(());
+        };
+        init.kill_all(signal)
+    }
+
+    /// Sends `SIGTERM` to every process in the namespace and kills the namespace if it is still
+    /// running after `timeout`.
+    pub fn stop(&self, timeout: Duration) -> std::io::Result<()> {
+        let Some(init) = self.init()? else {
+            return Ok(());
+        };
+        init.kill_all(libc::SIGTERM)?;
+        if linux::wait_exit(&init.pidfd, timeout)? {
+            return Ok(());
+        }
+        log::warn!("PID namespace {} did not stop in time, kill it", init.pid);
+        linux::pidfd_send_signal(&init.pidfd, libc::SIGKILL)
+    }
+}
+
+impl Init {
+    fn kill_all(&self, signal: libc::c_int) -> std::io::Result<()> {
+        linux::pidfd_send_signal(&self.pidfd, signal)?;
+        for pid in linux::pid_namespace_members(self.pid)?.unwrap_or_default() {
+            if pid != self.pid {
+                linux::kill(pid, signal)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -27,14 +126,18 @@ where
             libc::CLONE_NEWPID,
             unshare_pid_ns,
             SharedData {
-                next: Some(self.0),
+                next: Some(self.next),
                 ctx,
             },
         )
         .map_err(PidNamespaceError::ChildError)
         .unwrap();
-        res.join().unwrap().map_err(PidNamespaceError::ChildError)?;
-        Ok(())
+        self.handle.set_init(res.pid);
+        let res = res.join();
+        self.handle.clear_init();
+        // The process does not return a result if it was killed
+        res.ok_or(PidNamespaceError::Killed)?
+            .map_err(PidNamespaceError::ChildError)
     }
 }
 
@@ -53,6 +156,8 @@ where
 {
     #[error("Error creating pid namespace {0}")]
     ChildError(S),
+    #[error("PID namespace was killed")]
+    Killed,
 }
 
 fn unshare_pid_ns<S>(data: &mut SharedData<S>) -> (i32, Result<(), <S as Step>::Error>)
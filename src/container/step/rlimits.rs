@@ -0,0 +1,106 @@
+use crate::{
+    container::{step::Step, Context},
+    linux,
+};
+
+/// Value which removes the limit.
+pub const UNLIMITED: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    /// Size of the address space in bytes.
+    As,
+    /// Size of core dumps in bytes.
+    Core,
+    /// CPU time in seconds.
+    Cpu,
+    /// Size of files which can be created in bytes.
+    FSize,
+    /// Bytes of memory which can be locked.
+    MemLock,
+    /// Number of open file descriptors.
+    NoFile,
+    /// Number of processes of the real user id. This counts all processes of the user, not only
+    /// the ones of the container.
+    NProc,
+    /// Size of the stack in bytes.
+    Stack,
+}
+
+impl Resource {
+    fn raw(self) -> libc::c_int {
+        (match self {
+            Self::As => libc::RLIMIT_AS,
+            Self::Core => libc::RLIMIT_CORE,
+            Self::Cpu => libc::RLIMIT_CPU,
+            Self::FSize => libc::RLIMIT_FSIZE,
+            Self::MemLock => libc::RLIMIT_MEMLOCK,
+            Self::NoFile => libc::RLIMIT_NOFILE,
+            Self::NProc => libc::RLIMIT_NPROC,
+            Self::Stack => libc::RLIMIT_STACK,
+        }) as libc::c_int
+    }
+}
+
+/// Sets resource limits for the following steps and the command. Limits are inherited by all
+/// child processes.
+///
+/// Raising a hard limit requires `CAP_SYS_RESOURCE` in the initial user namespace, so limits can
+/// usually only be lowered.
+pub struct Rlimits<S>
+where
+    S: Step,
+{
+    limits: Vec<(Resource, u64, u64)>,
+    next: S,
+}
+
+impl<S> Rlimits<S>
+where
+    S: Step,
+{
+    pub fn new(limits: Vec<(Resource, u64, u64)>, next: S) -> Self {
+        Self { limits, next }
+    }
+
+    /// Sets the `soft` and `hard` limit of `resource`. Use [`UNLIMITED`] to remove a limit.
+    pub fn with_limit(mut self, resource: Resource, soft: u64, hard: u64) -> Self {
+        self.limits.push((resource, soft, hard));
+        self
+    }
+}
+
+impl<S> Step for Rlimits<S>
+where
+    S: Step,
+{
+    type Error = RlimitsError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        for (resource, soft, hard) in self.limits {
+            if soft > hard {
+                return Err(RlimitsError::SoftAboveHard(resource));
+            }
+            log::trace!("Set limit of {resource:?} to {soft}/{hard}");
+            linux::set_rlimit(resource.raw(), soft, hard)
+                .map_err(|error| RlimitsError::Set { resource, error })?;
+        }
+        self.next.run(ctx).map_err(RlimitsError::ChildError)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RlimitsError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Soft limit of {0:?} is above the hard limit")]
+    SoftAboveHard(Resource),
+    #[error("Failed to set limit of {resource:?}: {error}")]
+    Set {
+        resource: Resource,
+        error: std::io::Error,
+    },
+    #[error(transparent)]
+    ChildError(E),
+}
@@ -1,4 +1,9 @@
-use crate::container::{step::Step, Context};
+use std::os::unix::process::CommandExt as _;
+
+use crate::{
+    container::{step::Step, Context},
+    linux,
+};
 
 pub struct RunCommand {
     command: std::process::Command,
@@ -12,12 +17,55 @@ impl RunCommand {
 
 impl Step for RunCommand {
     type Error = std::io::Error;
-    fn run(mut self, _: &mut Context) -> Result<(), Self::Error> {
+    fn run(mut self, ctx: &mut Context) -> Result<(), Self::Error> {
         log::info!(
             "Started run command ${:?} {:?}",
             self.command.get_program(),
             self.command.get_args()
         );
+        let landlock = ctx
+            .landlock_rulesets()
+            .iter()
+            .map(|ruleset| ruleset.try_clone())
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let seccomp = ctx.seccomp_programs().to_vec();
+        let death_signal = ctx.parent_death_signal();
+        #[cfg(feature = "cap")]
+        let capabilities = ctx.capabilities().cloned();
+        #[cfg(not(feature = "cap"))]
+        let capabilities = None::<()>;
+        if !landlock.is_empty()
+            || !seccomp.is_empty()
+            || death_signal.is_some()
+            || capabilities.is_some()
+        {
+            let parent = std::process::id();
+            // Applied in the forked child, so only the command is restricted and not the
+            // process running the steps.
+            unsafe {
+                self.command.pre_exec(move || {
+                    if let Some(signal) = death_signal {
+                        linux::set_parent_death_signal(signal)?;
+                        // The parent may have exited before the signal was set.
+                        if libc::getppid() as u32 != parent {
+                            libc::raise(signal);
+                        }
+                    }
+                    // Steps changing the user ids may have cleared the capabilities
+                    #[cfg(feature = "cap")]
+                    if let Some(capabilities) = &capabilities {
+                        capabilities.apply()?;
+                    }
+                    for ruleset in &landlock {
+                        ruleset.restrict_self()?;
+                    }
+                    for program in &seccomp {
+                        program.install()?;
+                    }
+                    Ok(())
+                })
+            };
+        }
         self.command.spawn()?.wait()?;
         Ok(())
     }
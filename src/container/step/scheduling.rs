@@ -0,0 +1,197 @@
+use crate::{
+    container::{step::Step, Context},
+    linux,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    Other,
+    Batch,
+    Idle,
+    /// Real time policy with a priority from 1 to 99.
+    Fifo(u8),
+    /// Real time policy with a priority from 1 to 99.
+    RoundRobin(u8),
+}
+
+impl Policy {
+    fn raw(self) -> (libc::c_int, libc::c_int) {
+        match self {
+            Self::Other => (libc::SCHED_OTHER, 0),
+            Self::Batch => (libc::SCHED_BATCH, 0),
+            Self::Idle => (libc::SCHED_IDLE, 0),
+            Self::Fifo(priority) => (libc::SCHED_FIFO, priority as libc::c_int),
+            Self::RoundRobin(priority) => (libc::SCHED_RR, priority as libc::c_int),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// Real time class with a level from 0 (highest) to 7.
+    RealTime(u8),
+    /// Best effort class with a level from 0 (highest) to 7.
+    BestEffort(u8),
+    Idle,
+}
+
+impl IoPriority {
+    fn raw(self) -> (libc::c_int, libc::c_int) {
+        match self {
+            Self::RealTime(level) => (1, level as libc::c_int),
+            Self::BestEffort(level) => (2, level as libc::c_int),
+            Self::Idle => (3, 0),
+        }
+    }
+}
+
+/// Sets the CPU affinity, scheduling policy, nice value, I/O priority and OOM score adjustment
+/// of the following steps and the command. All settings are inherited by child processes.
+///
+/// Raising priorities, real time policies and negative OOM score adjustments require
+/// `CAP_SYS_NICE` or `CAP_SYS_RESOURCE` in the initial user namespace.
+pub struct Scheduling<S>
+where
+    S: Step,
+{
+    cpu_affinity: Option<Vec<usize>>,
+    policy: Option<Policy>,
+    nice: Option<i32>,
+    io_priority: Option<IoPriority>,
+    oom_score_adj: Option<i32>,
+    next: S,
+}
+
+impl<S> Scheduling<S>
+where
+    S: Step,
+{
+    pub fn new(next: S) -> Self {
+        Self {
+            cpu_affinity: None,
+            policy: None,
+            nice: None,
+            io_priority: None,
+            oom_score_adj: None,
+            next,
+        }
+    }
+
+    /// Restricts the processes to the CPUs with the given indices.
+    pub fn with_cpu_affinity(mut self, cpus: Vec<usize>) -> Self {
+        self.cpu_affinity = Some(cpus);
+        self
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Nice value from -20 (highest priority) to 19. Only used by non real time policies.
+    pub fn with_nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    pub fn with_io_priority(mut self, priority: IoPriority) -> Self {
+        self.io_priority = Some(priority);
+        self
+    }
+
+    /// Value from -1000 to 1000 added to the OOM score. -1000 disables the OOM killer for the
+    /// processes.
+    pub fn with_oom_score_adj(mut self, score: i32) -> Self {
+        self.oom_score_adj = Some(score);
+        self
+    }
+
+    fn validate<E>(&self) -> Result<(), SchedulingError<E>>
+    where
+        E: std::error::Error,
+    {
+        if let Some(Policy::Fifo(priority) | Policy::RoundRobin(priority)) = self.policy {
+            if !(1..=99).contains(&priority) {
+                return Err(SchedulingError::InvalidPriority(priority));
+            }
+        }
+        if let Some(nice) = self.nice {
+            if !(-20..=19).contains(&nice) {
+                return Err(SchedulingError::InvalidNice(nice));
+            }
+        }
+        if let Some(IoPriority::RealTime(level) | IoPriority::BestEffort(level)) = self.io_priority
+        {
+            if level > 7 {
+                return Err(SchedulingError::InvalidIoLevel(level));
+            }
+        }
+        if let Some(score) = self.oom_score_adj {
+            if !(-1000..=1000).contains(&score) {
+                return Err(SchedulingError::InvalidOomScoreAdj(score));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S> Step for Scheduling<S>
+where
+    S: Step,
+{
+    type Error = SchedulingError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        self.validate()?;
+        if let Some(cpus) = &self.cpu_affinity {
+            log::trace!("Set CPU affinity to {cpus:?}");
+            linux::set_cpu_affinity(cpus).map_err(SchedulingError::CpuAffinity)?;
+        }
+        if let Some(policy) = self.policy {
+            log::trace!("Set scheduling policy to {policy:?}");
+            let (policy, priority) = policy.raw();
+            linux::set_scheduler(policy, priority).map_err(SchedulingError::Policy)?;
+        }
+        if let Some(nice) = self.nice {
+            log::trace!("Set nice value to {nice}");
+            linux::set_nice(nice).map_err(SchedulingError::Nice)?;
+        }
+        if let Some(priority) = self.io_priority {
+            log::trace!("Set I/O priority to {priority:?}");
+            let (class, level) = priority.raw();
+            linux::set_io_priority(class, level).map_err(SchedulingError::IoPriority)?;
+        }
+        if let Some(score) = self.oom_score_adj {
+            log::trace!("Set OOM score adjustment to {score}");
+            linux::set_oom_score_adj(score).map_err(SchedulingError::OomScoreAdj)?;
+        }
+        self.next.run(ctx).map_err(SchedulingError::ChildError)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SchedulingError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Real time priority {0} is not between 1 and 99")]
+    InvalidPriority(u8),
+    #[error("Nice value {0} is not between -20 and 19")]
+    InvalidNice(i32),
+    #[error("I/O priority level {0} is not between 0 and 7")]
+    InvalidIoLevel(u8),
+    #[error("OOM score adjustment {0} is not between -1000 and 1000")]
+    InvalidOomScoreAdj(i32),
+    #[error("Failed to set CPU affinity: {0}")]
+    CpuAffinity(std::io::Error),
+    #[error("Failed to set scheduling policy: {0}")]
+    Policy(std::io::Error),
+    #[error("Failed to set nice value: {0}")]
+    Nice(std::io::Error),
+    #[error("Failed to set I/O priority: {0}")]
+    IoPriority(std::io::Error),
+    #[error("Failed to set OOM score adjustment: {0}")]
+    OomScoreAdj(std::io::Error),
+    #[error(transparent)]
+    ChildError(E),
+}
@@ -0,0 +1,210 @@
+use std::{cell::Cell, os::fd::AsRawFd as _};
+
+use crate::{
+    container::{step::Step, Context},
+    linux::{
+        self,
+        seccomp::{Listener, Policy, Program, SeccompCompileError},
+    },
+};
+
+pub use crate::linux::seccomp::{Notification, Response};
+
+const MSG_CONTINUE: usize = 1;
+const MSG_ABORT: usize = 2;
+
+/// Decides about syscalls which hit a rule with [`Action::Notify`](super::seccomp::Action).
+pub trait NotifyHandler {
+    fn handle(&mut self, notification: &Notification) -> Response;
+}
+
+impl<F> NotifyHandler for F
+where
+    F: FnMut(&Notification) -> Response,
+{
+    fn handle(&mut self, notification: &Notification) -> Response {
+        self(notification)
+    }
+}
+
+/// Runs the next step in a child process restricted by `policy`. Syscalls matching a rule with
+/// [`Action::Notify`](super::seccomp::Action) are passed to `handler`, which runs in the parent
+/// process and can allow, deny or emulate them.
+///
+/// Unlike [`Seccomp`](super::seccomp::Seccomp) the filter also applies to the following steps,
+/// so this step should be placed right before the command is run. Because parent and child share
+/// their memory, the handler must not wait for locks the child could hold while it is stopped in
+/// a syscall. Notifying syscalls like `mmap` or `futex` can therefore deadlock.
+pub struct SeccompNotify<S, H>
+where
+    S: Step,
+    H: NotifyHandler,
+{
+    policy: Policy,
+    handler: H,
+    next: S,
+}
+
+impl<S, H> SeccompNotify<S, H>
+where
+    S: Step,
+    H: NotifyHandler,
+{
+    pub fn new(policy: Policy, handler: H, next: S) -> Self {
+        Self {
+            policy,
+            handler,
+            next,
+        }
+    }
+}
+
+impl<S, H> Step for SeccompNotify<S, H>
+where
+    S: Step,
+    H: NotifyHandler,
+{
+    type Error = SeccompNotifyError<S::Error>;
+
+    fn run(mut self, ctx: &mut Context) -> Result<(), Self::Error> {
+        log::trace!("Run next step with seccomp supervisor");
+        let program = self.policy.compile()?;
+        let msg_queue_ctp = linux::EventFd::new().map_err(|_| SeccompNotifyError::MsgQueue)?;
+        let listener = Cell::new(None);
+        let shared_data = SharedData {
+            next: Some(self.next),
+            program,
+            msg_queue_ctp: msg_queue_ctp.clone(),
+            listener: &listener,
+            ctx,
+        };
+        let join_handle = linux::clone_vm_with_namespaces(0, notify_vm, shared_data)?;
+
+        log::debug!("Wait for Signal");
+        let msg = msg_queue_ctp
+            .receive()
+            .map_err(|_| SeccompNotifyError::MsgQueue)?;
+        let supervised = match (msg, listener.take()) {
+            (MSG_CONTINUE, Some(listener)) => {
+                supervise(&listener, join_handle.pid, &mut self.handler)
+            }
+            _ => Ok(()),
+        };
+        // The listener is closed at this point, so the child can not get stuck in a syscall
+        // waiting for a response.
+        let child_res = join_handle
+            .join()
+            .unwrap_or(Err(SeccompNotifyError::Killed));
+        supervised.map_err(SeccompNotifyError::Supervise)?;
+        child_res
+    }
+}
+
+/// Answers notifications until the child exits.
+fn supervise<H>(listener: &Listener, pid: libc::pid_t, handler: &mut H) -> std::io::Result<()>
+where
+    H: NotifyHandler,
+{
+    let pidfd = linux::pidfd_open(pid as u32)?;
+    let mut pollfds = [
+        libc::pollfd {
+            fd: listener.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: pidfd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        },
+    ];
+    loop {
+        pollfds.iter_mut().for_each(|pollfd| pollfd.revents = 0);
+        let res = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, -1) };
+        if res == -1 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        if pollfds[0].revents & libc::POLLIN != 0 {
+            let Some(notification) = listener.receive()? else {
+                continue;
+            };
+            log::debug!(
+                "Process {} called syscall {}",
+                notification.pid,
+                notification.syscall
+            );
+            let response = handler.handle(&notification);
+            listener.respond(notification.id, response)?;
+        } else if pollfds[0].revents != 0 || pollfds[1].revents != 0 {
+            log::debug!("Stop seccomp supervisor");
+            return Ok(());
+        }
+    }
+}
+
+struct SharedData<'a, S>
+where
+    S: Step,
+{
+    next: Option<S>,
+    program: Program,
+    msg_queue_ctp: linux::EventFd<usize>,
+    listener: &'a Cell<Option<Listener>>,
+    ctx: &'a mut Context,
+}
+
+fn notify_vm<S>(data: &mut SharedData<S>) -> (i32, Result<(), SeccompNotifyError<S::Error>>)
+where
+    S: Step,
+{
+    let msg = match data.program.install_with_listener() {
+        Ok(listener) => {
+            data.listener.set(Some(listener));
+            MSG_CONTINUE
+        }
+        Err(e) => {
+            log::error!("Failed to install seccomp filter: {e}");
+            if let Err(e) = data.msg_queue_ctp.send(MSG_ABORT) {
+                log::error!("Failed to send signal to parent: {e}");
+            }
+            return (1, Err(SeccompNotifyError::Install(e)));
+        }
+    };
+    if let Err(e) = data.msg_queue_ctp.send(msg) {
+        log::error!("Failed to send signal to parent: {e}");
+        return (1, Err(SeccompNotifyError::MsgQueue));
+    }
+    let res = data
+        .next
+        .take()
+        .expect("Component called twice")
+        .run(data.ctx)
+        .map_err(SeccompNotifyError::ChildError)
+        .inspect_err(|e| log::error!("{e}"));
+    (0, res)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SeccompNotifyError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Failed to compile seccomp policy: {0}")]
+    Compile(#[from] SeccompCompileError),
+    #[error(transparent)]
+    CloneError(#[from] linux::CloneError),
+    #[error("Failed to install seccomp filter: {0}")]
+    Install(std::io::Error),
+    #[error("Seccomp supervisor failed: {0}")]
+    Supervise(std::io::Error),
+    #[error("Error while using the message queue")]
+    MsgQueue,
+    #[error("Process was killed")]
+    Killed,
+    #[error(transparent)]
+    ChildError(E),
+}
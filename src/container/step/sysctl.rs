@@ -0,0 +1,218 @@
+use std::path::PathBuf;
+
+use crate::container::{step::Step, Context};
+
+/// IPC namespace sysctls outside of `fs.mqueue`.
+const IPC_KEYS: &[&str] = &[
+    "kernel.msgmax",
+    "kernel.msgmnb",
+    "kernel.msgmni",
+    "kernel.sem",
+    "kernel.shmall",
+    "kernel.shmmax",
+    "kernel.shmmni",
+    "kernel.shm_rmid_forced",
+];
+const UTS_KEYS: &[&str] = &["kernel.domainname", "kernel.hostname"];
+
+/// Writes sysctls through `/proc/sys`.
+///
+/// Only sysctls which are isolated by a namespace the container entered are accepted, so the
+/// host is never changed. `/proc` must be mounted, the values are written to the namespaces of
+/// the process running the step.
+///
+/// Keys use dots as separators. Keys with a component which contains a dot, like an interface
+/// `eth0.1`, can use slashes instead, e.g. `net/ipv4/conf/eth0.1/forwarding`.
+pub struct Sysctl<S>
+where
+    S: Step,
+{
+    values: Vec<(String, String)>,
+    next: S,
+}
+
+impl<S> Sysctl<S>
+where
+    S: Step,
+{
+    pub fn new(values: Vec<(String, String)>, next: S) -> Self {
+        Self { values, next }
+    }
+
+    pub fn with_value(mut self, key: String, value: String) -> Self {
+        self.values.push((key, value));
+        self
+    }
+}
+
+impl<S> Step for Sysctl<S>
+where
+    S: Step,
+{
+    type Error = SysctlError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        let values = self
+            .values
+            .iter()
+            .map(|(key, value)| Ok((key, path(key, ctx)?, value)))
+            .collect::<Result<Vec<_>, Self::Error>>()?;
+        for (key, path, value) in values {
+            log::debug!("Set sysctl {key} to {value}");
+            std::fs::write(path, value).map_err(|error| SysctlError::Write {
+                key: key.clone(),
+                error,
+            })?;
+        }
+        self.next.run(ctx).map_err(SysctlError::ChildError)
+    }
+}
+
+/// Path of `key` below `/proc/sys`, if `key` is namespaced by the namespaces of `ctx`.
+fn path<E>(key: &str, ctx: &Context) -> Result<PathBuf, SysctlError<E>>
+where
+    E: std::error::Error,
+{
+    let components = match key.contains('/') {
+        true => key.split('/').collect::<Vec<_>>(),
+        false => key.split('.').collect(),
+    };
+    if components
+        .iter()
+        .any(|c| c.is_empty() || *c == "." || *c == "..")
+    {
+        return Err(SysctlError::InvalidKey(key.to_string()));
+    }
+    let normalized = components.join(".");
+    let namespaced = match components[0] {
+        "net" => ctx.net(),
+        "user" => ctx.user(),
+        "fs" => components.get(1) == Some(&"mqueue") && ctx.ipc(),
+        "kernel" if IPC_KEYS.contains(&normalized.as_str()) => ctx.ipc(),
+        "kernel" if UTS_KEYS.contains(&normalized.as_str()) => ctx.uts(),
+        _ => false,
+    };
+    if !namespaced {
+        return Err(SysctlError::NotNamespaced(key.to_string()));
+    }
+    Ok(components
+        .iter()
+        .fold(PathBuf::from("/proc/sys"), |path, c| path.join(c)))
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SysctlError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Invalid sysctl {0}")]
+    InvalidKey(String),
+    #[error("Sysctl {0} is not isolated by the namespaces of the container")]
+    NotNamespaced(String),
+    #[error("Failed to set sysctl {key}: {error}")]
+    Write { key: String, error: std::io::Error },
+    #[error(transparent)]
+    ChildError(E),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(key: &str, ctx: &Context) -> Result<PathBuf, SysctlError<std::io::Error>> {
+        super::path(key, ctx)
+    }
+
+    fn all_namespaces() -> Context {
+        let mut ctx = Context::default();
+        ctx.set_ipc();
+        ctx.set_net();
+        ctx.set_uts();
+        ctx.entered_user_ns();
+        ctx
+    }
+
+    fn is_not_namespaced(res: Result<PathBuf, SysctlError<std::io::Error>>) -> bool {
+        matches!(res, Err(SysctlError::NotNamespaced(_)))
+    }
+
+    #[test]
+    fn ipc_keys_need_ipc_namespace() {
+        let mut ctx = Context::default();
+        assert!(is_not_namespaced(path("kernel.shmmax", &ctx)));
+        assert!(is_not_namespaced(path("fs.mqueue.msg_max", &ctx)));
+        ctx.set_ipc();
+        assert_eq!(
+            path("kernel.shmmax", &ctx).unwrap(),
+            PathBuf::from("/proc/sys/kernel/shmmax")
+        );
+        assert_eq!(
+            path("fs.mqueue.msg_max", &ctx).unwrap(),
+            PathBuf::from("/proc/sys/fs/mqueue/msg_max")
+        );
+    }
+
+    #[test]
+    fn uts_keys_need_uts_namespace() {
+        let mut ctx = Context::default();
+        ctx.set_ipc();
+        assert!(is_not_namespaced(path("kernel.hostname", &ctx)));
+        ctx.set_uts();
+        assert_eq!(
+            path("kernel.hostname", &ctx).unwrap(),
+            PathBuf::from("/proc/sys/kernel/hostname")
+        );
+    }
+
+    #[test]
+    fn net_keys_need_net_namespace() {
+        let mut ctx = Context::default();
+        assert!(is_not_namespaced(path("net.ipv4.ip_forward", &ctx)));
+        ctx.set_net();
+        assert_eq!(
+            path("net.ipv4.ip_forward", &ctx).unwrap(),
+            PathBuf::from("/proc/sys/net/ipv4/ip_forward")
+        );
+    }
+
+    #[test]
+    fn slashes_separate_components() {
+        let ctx = all_namespaces();
+        assert_eq!(
+            path("net/ipv4/conf/eth0.1/forwarding", &ctx).unwrap(),
+            PathBuf::from("/proc/sys/net/ipv4/conf/eth0.1/forwarding")
+        );
+    }
+
+    #[test]
+    fn host_global_keys_are_rejected() {
+        let ctx = all_namespaces();
+        for key in [
+            "vm.swappiness",
+            "vm/overcommit_memory",
+            "kernel.pid_max",
+            "kernel/pid_max",
+            "fs.file-max",
+        ] {
+            assert!(is_not_namespaced(path(key, &ctx)), "{key}");
+        }
+    }
+
+    #[test]
+    fn invalid_components_are_rejected() {
+        let ctx = all_namespaces();
+        for key in [
+            "",
+            "net..ipv4",
+            "net.ipv4.",
+            "net/ipv4/../../vm/swappiness",
+            "net/./ipv4",
+            "net//ipv4",
+        ] {
+            assert!(
+                matches!(path(key, &ctx), Err(SysctlError::InvalidKey(_))),
+                "{key}"
+            );
+        }
+    }
+}
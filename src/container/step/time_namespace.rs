@@ -0,0 +1,129 @@
+use std::io::{Read as _, Write as _};
+
+use crate::{
+    container::{step::Step, Context},
+    linux,
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ClockOffset {
+    secs: i64,
+    nanos: u32,
+}
+
+impl ClockOffset {
+    pub fn forward(offset: std::time::Duration) -> Self {
+        Self {
+            secs: offset.as_secs() as i64,
+            nanos: offset.subsec_nanos(),
+        }
+    }
+
+    pub fn backward(offset: std::time::Duration) -> Self {
+        let offset = Self::forward(offset);
+        if offset.nanos == 0 {
+            Self {
+                secs: -offset.secs,
+                nanos: 0,
+            }
+        } else {
+            Self {
+                secs: -offset.secs - 1,
+                nanos: 1_000_000_000 - offset.nanos,
+            }
+        }
+    }
+}
+
+/// Runs the next step in a new time namespace with `CLOCK_MONOTONIC` and `CLOCK_BOOTTIME`
+/// shifted by the given offsets.
+///
+/// The kernel only moves children into a new time namespace and does not allow them to share
+/// memory with the parent. Because of that the next step runs in a forked process. Everything
+/// the following steps record in the [`Context`] is lost when the process exits, e.g. the resource
+/// usage recorded by [`Cgroup`](super::cgroup::Cgroup), and their errors are only returned as
+/// message. Only wrap steps which record nothing for the caller; a warning is logged if recorded
+/// data is dropped.
+pub struct TimeNamespace<S>
+where
+    S: Step,
+{
+    next: S,
+    monotonic: ClockOffset,
+    boottime: ClockOffset,
+}
+
+impl<S> TimeNamespace<S>
+where
+    S: Step,
+{
+    pub fn new(monotonic: ClockOffset, boottime: ClockOffset, next: S) -> Self {
+        Self {
+            next,
+            monotonic,
+            boottime,
+        }
+    }
+}
+
+impl<S> Step for TimeNamespace<S>
+where
+    S: Step,
+{
+    type Error = TimeNamespaceError;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        ctx.set_time();
+        log::info!("Unshare time namespace");
+        nix::sched::unshare(nix::sched::CloneFlags::from_bits_retain(
+            libc::CLONE_NEWTIME,
+        ))
+        .map_err(TimeNamespaceError::Unshare)?;
+        let offsets = format!(
+            "monotonic {} {}\nboottime {} {}\n",
+            self.monotonic.secs, self.monotonic.nanos, self.boottime.secs, self.boottime.nanos
+        );
+        log::debug!("Writing time namespace offsets {offsets:?}");
+        std::fs::write("/proc/self/timens_offsets", offsets)
+            .map_err(TimeNamespaceError::Offsets)?;
+
+        let (mut rx, mut tx) =
+            std::os::unix::net::UnixStream::pair().map_err(TimeNamespaceError::Fork)?;
+        let next = self.next;
+        let status = linux::fork_and_wait(|| {
+            log::trace!("New time namespace {}", std::process::id());
+            let res = next.run(ctx);
+            if ctx.take_cgroup_usage().is_some() {
+                log::warn!("Dropping cgroup usage recorded in the time namespace");
+            }
+            match res {
+                Ok(()) => 0,
+                Err(e) => {
+                    log::error!("{e}");
+                    let _ = tx.write_all(e.to_string().as_bytes());
+                    1
+                }
+            }
+        })
+        .map_err(TimeNamespaceError::Fork)?;
+        drop(tx);
+        if status != 0 {
+            let mut msg = String::new();
+            let _ = rx.read_to_string(&mut msg);
+            return Err(TimeNamespaceError::ChildError { status, msg });
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TimeNamespaceError {
+    #[error("Failed to unshare {0}")]
+    Unshare(nix::errno::Errno),
+    #[error("Failed to write time namespace offsets: {0}")]
+    Offsets(std::io::Error),
+    #[error("Failed to fork into time namespace: {0}")]
+    Fork(std::io::Error),
+    #[error("Process in time namespace exited with {status}: {msg}")]
+    ChildError { status: i32, msg: String },
+}
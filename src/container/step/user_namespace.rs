@@ -1,4 +1,7 @@
+use std::cell::Cell;
 use std::fmt::Debug;
+use std::os::fd::AsFd as _;
+use std::path::PathBuf;
 
 use crate::container::Context;
 use crate::{container::MapType, linux};
@@ -10,6 +13,7 @@ pub struct UserNamespaceRoot<S> {
     uid_map: IdMap<User>,
     gid_map: IdMap<Group>,
     switch_to: Option<(u32, u32)>,
+    idmapped_mounts: Vec<(PathBuf, bool)>,
 }
 
 impl<S> UserNamespaceRoot<S> {
@@ -19,8 +23,21 @@ impl<S> UserNamespaceRoot<S> {
             uid_map: IdMap::new_with_current_user_as_root(),
             gid_map: IdMap::new_with_current_user_as_root(),
             switch_to: Some((0, 0)),
+            idmapped_mounts: Vec::new(),
         }
     }
+
+    /// Prepares a bind of `src` whose ids are mapped with the id maps of the new user namespace,
+    /// so files owned by a mapped host user are owned by the mapped user in the container. The
+    /// bind is attached by
+    /// [`MountOperation::IdmappedBindMount`](super::mount_namespace::MountOperation::IdmappedBindMount).
+    ///
+    /// The mount is created by the calling process, which needs `CAP_SYS_ADMIN` in the user
+    /// namespace owning the file system of `src`, usually the initial user namespace.
+    pub fn with_idmapped_mount(mut self, src: PathBuf, recursive: bool) -> Self {
+        self.idmapped_mounts.push((src, recursive));
+        self
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -56,6 +73,7 @@ impl<S> UserNamespaceRoot<S> {
             uid_map,
             gid_map,
             switch_to: user,
+            idmapped_mounts: Vec::new(),
         })
     }
 }
@@ -69,7 +87,9 @@ where
         log::trace!("Create user namespace");
         let msg_queue_ctp = linux::EventFd::new().unwrap();
         let msg_queue_ptc = linux::EventFd::new().unwrap();
+        let idmapped_mounts = Cell::new(Vec::new());
         let shared_data = SharedData {
+            idmapped_mounts: &idmapped_mounts,
             component: Some(self.next_step),
             msg_queue_ctp: msg_queue_ctp.clone(),
             msg_queue_ptc: msg_queue_ptc.clone(),
@@ -120,13 +140,50 @@ where
         log::debug!("Got Signal");
         write_id_map(self.uid_map, join_handle.pid)?;
         write_id_map(self.gid_map, join_handle.pid)?;
+        let mounts = prepare_idmapped_mounts(self.idmapped_mounts, join_handle.pid);
         log::debug!("Send Signal");
-        msg_queue_ptc.send(1).unwrap();
+        let mounts = match mounts {
+            Ok(mounts) => {
+                idmapped_mounts.set(mounts);
+                msg_queue_ptc.send(MSG_CONTINUE).unwrap();
+                None
+            }
+            Err(e) => {
+                msg_queue_ptc.send(MSG_ABORT).unwrap();
+                Some(e)
+            }
+        };
         // shared_data.ret can only be assumed to be set after the child has finished
         log::debug!("Wait for namespace");
-        let ctx = join_handle.join().unwrap()?;
-        Ok(ctx)
+        let ctx = join_handle.join().unwrap();
+        if let Some(e) = mounts {
+            return Err(BuildUserNamespaceRootError::IdmappedMount(e));
+        }
+        ctx
+    }
+}
+
+const MSG_CONTINUE: usize = 1;
+const MSG_ABORT: usize = 2;
+
+fn prepare_idmapped_mounts(
+    mounts: Vec<(PathBuf, bool)>,
+    pid: libc::pid_t,
+) -> Result<Vec<(PathBuf, linux::mount_api::Mount)>, linux::mount_api::MountApiError> {
+    if mounts.is_empty() {
+        return Ok(Vec::new());
     }
+    let user_ns = std::fs::File::open(format!("/proc/{pid}/ns/user"))
+        .map_err(|e| linux::mount_api::MountApiError::new("open user namespace", e))?;
+    mounts
+        .into_iter()
+        .map(|(src, recursive)| {
+            log::debug!("Create idmapped mount of {src:?}");
+            let mount = linux::mount_api::Mount::open_tree(&src, recursive)?;
+            mount.set_idmap(user_ns.as_fd(), recursive)?;
+            Ok((src, mount))
+        })
+        .collect()
 }
 
 #[derive(thiserror::Error)]
@@ -191,6 +248,7 @@ where
     msg_queue_ctp: linux::EventFd<usize>,
     msg_queue_ptc: linux::EventFd<usize>,
     switch_to: Option<(u32, u32)>,
+    idmapped_mounts: &'a Cell<Vec<(PathBuf, linux::mount_api::Mount)>>,
     ctx: &'a mut Context,
 }
 fn root_namespace_vm<S>(
@@ -205,11 +263,18 @@ where
         log::error!("Failed to send signal to parent: {e}");
         return (1, Err(BuildUserNamespaceRootError::MsgQueue));
     };
-    if let Err(e) = data.msg_queue_ptc.receive() {
-        log::error!("Failed to receive signal from parent: {e}");
-        return (1, Err(BuildUserNamespaceRootError::MsgQueue));
+    match data.msg_queue_ptc.receive() {
+        Ok(MSG_CONTINUE) => {}
+        Ok(_) => return (1, Err(BuildUserNamespaceRootError::Aborted)),
+        Err(e) => {
+            log::error!("Failed to receive signal from parent: {e}");
+            return (1, Err(BuildUserNamespaceRootError::MsgQueue));
+        }
     };
     log::debug!("Namespace resumed");
+    for (src, mount) in data.idmapped_mounts.take() {
+        data.ctx.add_idmapped_mount(src, mount);
+    }
     if let Some(user) = data.switch_to {
         linux::switch_user(user).unwrap();
         log::debug!("Switched to user uid: {} gid: {}", user.0, user.1)
@@ -236,4 +301,8 @@ pub enum BuildUserNamespaceRootError<E: std::error::Error> {
     GroupIdMapError(#[from] IdMapError<Group>),
     #[error("Error while using the message queue")]
     MsgQueue,
+    #[error("Failed to create idmapped mount: {0}")]
+    IdmappedMount(linux::mount_api::MountApiError),
+    #[error("Aborted by parent")]
+    Aborted,
 }
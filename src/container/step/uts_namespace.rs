@@ -0,0 +1,68 @@
+use crate::{
+    container::{step::Step, Context},
+    linux,
+};
+
+pub struct UtsNamespace<S>
+where
+    S: Step,
+{
+    next: S,
+    hostname: Option<String>,
+    domainname: Option<String>,
+}
+
+impl<S> UtsNamespace<S>
+where
+    S: Step,
+{
+    pub fn new(hostname: Option<String>, domainname: Option<String>, next: S) -> Self {
+        Self {
+            next,
+            hostname,
+            domainname,
+        }
+    }
+
+    pub fn with_hostname(hostname: String, next: S) -> Self {
+        Self::new(Some(hostname), None, next)
+    }
+}
+
+impl<S> Step for UtsNamespace<S>
+where
+    S: Step,
+{
+    type Error = UtsNamespaceError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        ctx.set_uts();
+        log::info!("Unshare uts namespace");
+        nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWUTS)
+            .map_err(UtsNamespaceError::Unshare)?;
+        if let Some(hostname) = &self.hostname {
+            log::debug!("Set hostname to {hostname}");
+            linux::set_hostname(hostname).map_err(UtsNamespaceError::Hostname)?;
+        }
+        if let Some(domainname) = &self.domainname {
+            log::debug!("Set domainname to {domainname}");
+            linux::set_domainname(domainname).map_err(UtsNamespaceError::Domainname)?;
+        }
+        self.next.run(ctx).map_err(UtsNamespaceError::ChildError)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UtsNamespaceError<E>
+where
+    E: std::error::Error,
+{
+    #[error("Failed to unshare {0}")]
+    Unshare(nix::errno::Errno),
+    #[error("Failed to set hostname: {0}")]
+    Hostname(std::io::Error),
+    #[error("Failed to set domainname: {0}")]
+    Domainname(std::io::Error),
+    #[error(transparent)]
+    ChildError(E),
+}
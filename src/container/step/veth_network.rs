@@ -0,0 +1,235 @@
+use std::net::IpAddr;
+
+use crate::{
+    container::{step::Step, Context},
+    linux::{self, netlink::Netlink},
+};
+
+const MSG_CONTINUE: usize = 1;
+const MSG_ABORT: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterfaceAddress {
+    pub address: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl InterfaceAddress {
+    pub fn new(address: IpAddr, prefix_len: u8) -> Self {
+        Self {
+            address,
+            prefix_len,
+        }
+    }
+}
+
+/// Runs the next step in a new network namespace which is connected to the network namespace of
+/// the caller by a veth pair.
+///
+/// The veth pair is created by the parent process, so the parent needs `CAP_NET_ADMIN` in its
+/// network namespace.
+pub struct VethNetwork<S>
+where
+    S: Step,
+{
+    next: S,
+    host_interface: String,
+    container_interface: String,
+    host_addresses: Vec<InterfaceAddress>,
+    container_addresses: Vec<InterfaceAddress>,
+    default_routes: Vec<IpAddr>,
+    bridge: Option<String>,
+}
+
+impl<S> VethNetwork<S>
+where
+    S: Step,
+{
+    pub fn new(host_interface: String, container_interface: String, next: S) -> Self {
+        Self {
+            next,
+            host_interface,
+            container_interface,
+            host_addresses: Vec::new(),
+            container_addresses: Vec::new(),
+            default_routes: Vec::new(),
+            bridge: None,
+        }
+    }
+
+    pub fn with_host_address(mut self, address: InterfaceAddress) -> Self {
+        self.host_addresses.push(address);
+        self
+    }
+
+    pub fn with_container_address(mut self, address: InterfaceAddress) -> Self {
+        self.container_addresses.push(address);
+        self
+    }
+
+    pub fn with_default_route(mut self, gateway: IpAddr) -> Self {
+        self.default_routes.push(gateway);
+        self
+    }
+
+    pub fn with_bridge(mut self, bridge: String) -> Self {
+        self.bridge = Some(bridge);
+        self
+    }
+}
+
+impl<S> Step for VethNetwork<S>
+where
+    S: Step,
+{
+    type Error = VethNetworkError<S::Error>;
+
+    fn run(self, ctx: &mut Context) -> Result<(), Self::Error> {
+        log::trace!("Create network namespace with veth pair");
+        let msg_queue_ctp = linux::EventFd::new().map_err(|_| VethNetworkError::MsgQueue)?;
+        let msg_queue_ptc = linux::EventFd::new().map_err(|_| VethNetworkError::MsgQueue)?;
+        let shared_data = SharedData {
+            next: Some(self.next),
+            msg_queue_ctp: msg_queue_ctp.clone(),
+            msg_queue_ptc: msg_queue_ptc.clone(),
+            container_interface: self.container_interface.clone(),
+            container_addresses: self.container_addresses,
+            default_routes: self.default_routes,
+            ctx,
+        };
+        let join_handle =
+            linux::clone_vm_with_namespaces(libc::CLONE_NEWNET, net_namespace_vm, shared_data)?;
+
+        log::debug!("Wait for Signal");
+        msg_queue_ctp
+            .receive()
+            .map_err(|_| VethNetworkError::MsgQueue)?;
+        let res = configure_host(
+            &self.host_interface,
+            &self.container_interface,
+            &self.host_addresses,
+            self.bridge.as_deref(),
+            join_handle.pid,
+        );
+        let msg = if res.is_ok() { MSG_CONTINUE } else { MSG_ABORT };
+        log::debug!("Send Signal");
+        msg_queue_ptc
+            .send(msg)
+            .map_err(|_| VethNetworkError::MsgQueue)?;
+        // The process does not return a result if it was killed
+        let child_res = join_handle.join().unwrap_or(Err(VethNetworkError::Killed));
+        res.map_err(VethNetworkError::Host)?;
+        child_res
+    }
+}
+
+fn configure_host(
+    host_interface: &str,
+    container_interface: &str,
+    addresses: &[InterfaceAddress],
+    bridge: Option<&str>,
+    pid: libc::pid_t,
+) -> std::io::Result<()> {
+    let mut netlink = Netlink::new()?;
+    netlink.create_veth(host_interface, container_interface, pid)?;
+    let host_index = netlink.link_index(host_interface)?;
+    for address in addresses {
+        netlink.add_address(host_index, address.address, address.prefix_len)?;
+    }
+    if let Some(bridge) = bridge {
+        let bridge_index = netlink.link_index(bridge)?;
+        netlink.set_link_master(host_index, bridge_index)?;
+    }
+    netlink.set_link_up(host_index)
+}
+
+fn configure_container(
+    interface: &str,
+    addresses: &[InterfaceAddress],
+    default_routes: &[IpAddr],
+) -> std::io::Result<()> {
+    let mut netlink = Netlink::new()?;
+    let lo = netlink.link_index("lo")?;
+    netlink.set_link_up(lo)?;
+    let index = netlink.link_index(interface)?;
+    for address in addresses {
+        netlink.add_address(index, address.address, address.prefix_len)?;
+    }
+    netlink.set_link_up(index)?;
+    for gateway in default_routes {
+        netlink.add_default_route(*gateway, index)?;
+    }
+    Ok(())
+}
+
+struct SharedData<'a, S>
+where
+    S: Step,
+{
+    next: Option<S>,
+    msg_queue_ctp: linux::EventFd<usize>,
+    msg_queue_ptc: linux::EventFd<usize>,
+    container_interface: String,
+    container_addresses: Vec<InterfaceAddress>,
+    default_routes: Vec<IpAddr>,
+    ctx: &'a mut Context,
+}
+
+fn net_namespace_vm<S>(data: &mut SharedData<S>) -> (i32, Result<(), VethNetworkError<S::Error>>)
+where
+    S: Step,
+{
+    data.ctx.set_net();
+    if let Err(e) = data.msg_queue_ctp.send(MSG_CONTINUE) {
+        log::error!("Failed to send signal to parent: {e}");
+        return (1, Err(VethNetworkError::MsgQueue));
+    };
+    match data.msg_queue_ptc.receive() {
+        Ok(MSG_CONTINUE) => {}
+        Ok(_) => {
+            log::error!("Parent failed to set up veth pair");
+            return (1, Err(VethNetworkError::Aborted));
+        }
+        Err(e) => {
+            log::error!("Failed to receive signal from parent: {e}");
+            return (1, Err(VethNetworkError::MsgQueue));
+        }
+    };
+    if let Err(e) = configure_container(
+        &data.container_interface,
+        &data.container_addresses,
+        &data.default_routes,
+    ) {
+        log::error!("Failed to configure container network: {e}");
+        return (1, Err(VethNetworkError::Container(e)));
+    }
+    let res = data
+        .next
+        .take()
+        .expect("Component called twice")
+        .run(data.ctx)
+        .map_err(VethNetworkError::ChildError)
+        .inspect_err(|e| log::error!("{e}"));
+    (0, res)
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum VethNetworkError<E>
+where
+    E: std::error::Error,
+{
+    #[error(transparent)]
+    CloneError(#[from] linux::CloneError),
+    #[error("Failed to configure host side of veth pair: {0}")]
+    Host(std::io::Error),
+    #[error("Failed to configure container side of veth pair: {0}")]
+    Container(std::io::Error),
+    #[error("Network setup was aborted by the parent")]
+    Aborted,
+    #[error("Error while using the message queue")]
+    MsgQueue,
+    #[error("Network namespace process was killed")]
+    Killed,
+    #[error(transparent)]
+    ChildError(E),
+}
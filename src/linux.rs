@@ -1,16 +1,26 @@
 use core::panic;
 use std::{
     fmt::{Debug, Display},
-    os::{fd::FromRawFd, unix::ffi::OsStrExt},
+    os::{
+        fd::{AsRawFd as _, FromRawFd},
+        unix::{ffi::OsStrExt, fs::MetadataExt as _},
+    },
     path::PathBuf,
+    time::Duration,
 };
 
 use nix::errno::Errno;
 
+pub mod cgroup;
+pub mod landlock;
 #[cfg(feature = "cap")]
 pub mod libcap;
+pub mod mount_api;
+pub mod netlink;
+pub mod seccomp;
 
 const EXPECT_RAW_OS_ERROR: &str = "Syscall failed with undefined error code";
+const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug, thiserror::Error)]
 pub enum UnshareError {
@@ -57,9 +67,68 @@ pub struct ProcessHandle<T, R> {
     pub(super) pid: libc::pid_t,
     stack_ptr: *mut libc::c_void,
     args: *mut CloneArgs<T, R>,
+    cgroup: Option<cgroup::Cgroup>,
+    stop_timeout: Duration,
 }
 
 impl<T, R> ProcessHandle<T, R> {
+    /// Lets [`Self::kill_all`] use `cgroup` to find all processes of the container. The process
+    /// must be the only user of `cgroup`.
+    pub fn set_cgroup(&mut self, cgroup: cgroup::Cgroup) {
+        self.cgroup = Some(cgroup);
+    }
+
+    /// Time the handle waits after `SIGTERM` before sending `SIGKILL`.
+    pub fn set_stop_timeout(&mut self, timeout: Duration) {
+        self.stop_timeout = timeout;
+    }
+
+    /// Sends `signal` to the process and all of its descendants. Without a cgroup, the
+    /// processes of the PID namespace of the process are signaled. Only if the process uses no
+    /// PID namespace of its own, its descendants are found by their parent pids, which misses
+    /// processes reparented away from the process.
+    pub fn kill_all(&self, signal: libc::c_int) -> std::io::Result<()> {
+        if let Some(cgroup) = &self.cgroup {
+            match cgroup.signal_all(signal) {
+                Ok(()) => return Ok(()),
+                Err(e) => log::warn!("Failed to signal processes of cgroup: {e}"),
+            }
+        }
+        let pids = match pid_namespace_members(self.pid) {
+            Ok(Some(pids)) => pids,
+            Ok(None) => descendants(self.pid)?,
+            Err(e) => {
+                log::warn!("Failed to find PID namespace of process {}: {e}", self.pid);
+                descendants(self.pid)?
+            }
+        };
+        // The init process of the namespace is among the pids. Once it is killed, the kernel
+        // kills every other process of the namespace, even the ones forked in the meantime.
+        for pid in pids {
+            kill(pid, signal)?;
+        }
+        kill(self.pid, signal)
+    }
+
+    /// Stops the process and all of its descendants. `SIGTERM` is sent first, processes which
+    /// are still alive after the stop timeout are killed.
+    fn terminate(&mut self) {
+        log::info!("Stopping namespace process {}", self.pid);
+        if let Err(e) = self.kill_all(libc::SIGTERM) {
+            log::error!("Failed to send SIGTERM: {e}");
+        }
+        match wait_timeout(self.pid, self.stop_timeout) {
+            Ok(true) => return,
+            Ok(false) => log::warn!("Process {} did not stop in time, kill it", self.pid),
+            Err(e) => log::error!("Failed to wait for process {}: {e}", self.pid),
+        }
+        if let Err(e) = self.kill_all(libc::SIGKILL) {
+            log::error!("Failed to send SIGKILL: {e}");
+        }
+        let mut status = -1;
+        unsafe { libc::waitpid(self.pid, &mut status, 0) };
+    }
+
     /// TODO: Change return to result
     pub fn join(mut self) -> Option<R> {
         log::info!("Waiting for namespace process {}", self.pid);
@@ -86,14 +155,124 @@ impl<T, R> ProcessHandle<T, R> {
 impl<T, R> Drop for ProcessHandle<T, R> {
     fn drop(&mut self) {
         if self.pid != 0 {
-            let mut status = -1;
-            unsafe { libc::kill(self.pid, libc::SIGTERM) };
-            unsafe { libc::waitpid(self.pid, &mut status, 0) };
+            self.terminate();
             self.pid = 0;
         }
     }
 }
 
+/// Waits up to `timeout` for `pid` to exit and reaps it. Returns `false` if the process is still
+/// running.
+fn wait_timeout(pid: libc::pid_t, timeout: Duration) -> std::io::Result<bool> {
+    let pidfd = pidfd_open(pid as u32)?;
+    if !wait_exit(&pidfd, timeout)? {
+        return Ok(false);
+    }
+    let mut status = -1;
+    unsafe { libc::waitpid(pid, &mut status, 0) };
+    Ok(true)
+}
+
+/// Waits up to `timeout` for the process of `pidfd` to exit without reaping it. Returns `false`
+/// if the process is still running.
+pub(crate) fn wait_exit(pidfd: &std::fs::File, timeout: Duration) -> std::io::Result<bool> {
+    let mut pollfd = libc::pollfd {
+        fd: pidfd.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let res = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as i32) };
+    match res {
+        -1 => Err(std::io::Error::last_os_error()),
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+pub(crate) fn kill(pid: libc::pid_t, signal: libc::c_int) -> std::io::Result<()> {
+    let res = unsafe { libc::kill(pid, signal) };
+    if res == -1 {
+        let err = std::io::Error::last_os_error();
+        // The process already exited
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            return Ok(());
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// All processes in the PID namespace of `pid` and in the namespace the children of `pid` are
+/// created in, unless they are the PID namespace of the calling process. `None` if both are.
+pub(crate) fn pid_namespace_members(pid: libc::pid_t) -> std::io::Result<Option<Vec<libc::pid_t>>> {
+    let namespace = |pid: &str, link: &str| {
+        std::fs::metadata(format!("/proc/{pid}/ns/{link}")).map(|meta| meta.ino())
+    };
+    let own = namespace("self", "pid")?;
+    let pid = pid.to_string();
+    // The namespace for children can not be read before the first child was created
+    let container: Vec<u64> = [namespace(&pid, "pid")?]
+        .into_iter()
+        .chain(namespace(&pid, "pid_for_children").ok())
+        .filter(|ns| *ns != own)
+        .collect();
+    if container.is_empty() {
+        return Ok(None);
+    }
+    let mut members = Vec::new();
+    for entry in std::fs::read_dir("/proc")? {
+        let entry = entry?;
+        let Some(member) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<libc::pid_t>().ok())
+        else {
+            continue;
+        };
+        // The process might have exited in the meantime
+        if namespace(&member.to_string(), "pid").is_ok_and(|ns| container.contains(&ns)) {
+            members.push(member);
+        }
+    }
+    Ok(Some(members))
+}
+
+/// All descendants of `pid`, found by walking the parent pids of all processes in `/proc`.
+pub(crate) fn descendants(pid: libc::pid_t) -> std::io::Result<Vec<libc::pid_t>> {
+    let mut children = std::collections::HashMap::<libc::pid_t, Vec<libc::pid_t>>::new();
+    for entry in std::fs::read_dir("/proc")? {
+        let Some(child) = entry?
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse::<libc::pid_t>().ok())
+        else {
+            continue;
+        };
+        // The process might have exited in the meantime
+        let Ok(stat) = std::fs::read_to_string(format!("/proc/{child}/stat")) else {
+            continue;
+        };
+        // The command name is wrapped in parentheses and might contain spaces
+        let Some(parent) = stat
+            .rsplit_once(')')
+            .and_then(|(_, rest)| rest.split_whitespace().nth(1))
+            .and_then(|ppid| ppid.parse().ok())
+        else {
+            continue;
+        };
+        children.entry(parent).or_default().push(child);
+    }
+    let mut descendants = Vec::new();
+    let mut queue = vec![pid];
+    while let Some(pid) = queue.pop() {
+        if let Some(children) = children.get(&pid) {
+            descendants.extend_from_slice(children);
+            queue.extend_from_slice(children);
+        }
+    }
+    Ok(descendants)
+}
+
 pub fn clone_vm_with_namespaces<T, R>(
     flags: i32,
     f: fn(&mut T) -> (i32, R),
@@ -138,10 +317,42 @@ pub fn clone_vm_with_namespaces<T, R>(
             pid,
             stack_ptr: stack,
             args,
+            cgroup: None,
+            stop_timeout: DEFAULT_STOP_TIMEOUT,
         }),
     }
 }
 
+/// Runs `f` in a forked child process which does not share memory with the caller and waits
+/// for it to exit. Returns the exit status of the child.
+pub(crate) fn fork_and_wait<F>(f: F) -> std::io::Result<i32>
+where
+    F: FnOnce() -> i32,
+{
+    log::trace!("fork new process");
+    let pid = unsafe { libc::fork() };
+    match pid {
+        -1 => Err(std::io::Error::last_os_error()),
+        0 => {
+            let code = f();
+            unsafe { libc::_exit(code) }
+        }
+        pid => {
+            log::info!("Waiting for forked process {pid}");
+            let mut status = -1;
+            let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+            if res == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            if libc::WIFEXITED(status) {
+                Ok(libc::WEXITSTATUS(status))
+            } else {
+                Ok(128 + libc::WTERMSIG(status))
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 struct CloneArgs<T, R> {
     fn_args: T,
@@ -287,6 +498,12 @@ impl<T> EventFd<T> {
     }
 }
 
+impl<T> std::os::fd::AsRawFd for EventFd<T> {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.event_fd
+    }
+}
+
 impl<T> Clone for EventFd<T> {
     fn clone(&self) -> Self {
         Self {
@@ -430,6 +647,153 @@ pub fn mount(
     Ok(())
 }
 
+pub(crate) fn children_rusage() -> libc::rusage {
+    let mut usage = unsafe { std::mem::zeroed() };
+    unsafe { libc::getrusage(libc::RUSAGE_CHILDREN, &mut usage) };
+    usage
+}
+
+pub(crate) fn set_hostname(name: &str) -> std::io::Result<()> {
+    let res = unsafe { libc::sethostname(name.as_ptr() as *const _, name.len()) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn set_domainname(name: &str) -> std::io::Result<()> {
+    let res = unsafe { libc::setdomainname(name.as_ptr() as *const _, name.len()) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn set_no_new_privs() -> std::io::Result<()> {
+    let res = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn get_dumpable() -> std::io::Result<bool> {
+    let res = unsafe { libc::prctl(libc::PR_GET_DUMPABLE, 0, 0, 0, 0) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(res != 0)
+}
+
+pub(crate) fn set_dumpable(dumpable: bool) -> std::io::Result<()> {
+    let res = unsafe { libc::prctl(libc::PR_SET_DUMPABLE, dumpable as libc::c_ulong, 0, 0, 0) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Sends `signal` to the calling process if its parent exits. The setting is cleared in the
+/// children created by fork and clone.
+pub(crate) fn set_parent_death_signal(signal: libc::c_int) -> std::io::Result<()> {
+    let res = unsafe { libc::prctl(libc::PR_SET_PDEATHSIG, signal as libc::c_ulong, 0, 0, 0) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn new_session() -> std::io::Result<()> {
+    let res = unsafe { libc::setsid() };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Replaces the session keyring of the calling process with a new anonymous keyring.
+pub(crate) fn join_new_session_keyring() -> std::io::Result<()> {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_keyctl,
+            libc::KEYCTL_JOIN_SESSION_KEYRING,
+            std::ptr::null::<libc::c_char>(),
+        )
+    };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn set_rlimit(resource: libc::c_int, soft: u64, hard: u64) -> std::io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    let res = unsafe { libc::setrlimit(resource as _, &limit) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn set_cpu_affinity(cpus: &[usize]) -> std::io::Result<()> {
+    let mut set = unsafe { std::mem::zeroed::<libc::cpu_set_t>() };
+    for cpu in cpus {
+        if *cpu >= libc::CPU_SETSIZE as usize {
+            return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+        }
+        unsafe { libc::CPU_SET(*cpu, &mut set) };
+    }
+    let res = unsafe { libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn set_scheduler(policy: libc::c_int, priority: libc::c_int) -> std::io::Result<()> {
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    let res = unsafe { libc::sched_setscheduler(0, policy, &param) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn set_nice(nice: libc::c_int) -> std::io::Result<()> {
+    let res = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+pub(crate) fn set_io_priority(class: libc::c_int, level: libc::c_int) -> std::io::Result<()> {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            (class << IOPRIO_CLASS_SHIFT) | level,
+        )
+    };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn set_oom_score_adj(score: i32) -> std::io::Result<()> {
+    std::fs::write("/proc/self/oom_score_adj", score.to_string())
+}
+
 pub(crate) fn get_user_name(uid: u32) -> Option<String> {
     let passwd = unsafe { libc::getpwuid(uid) };
     if passwd.is_null() {
@@ -441,7 +805,31 @@ pub(crate) fn get_user_name(uid: u32) -> Option<String> {
     Some(username.to_str().unwrap().to_string())
 }
 
-pub(crate) fn pidfd_open(pid: u32) -> std::fs::File {
+/// Sends `signal` to the process of `pidfd`. A process which already exited is not an error.
+pub(crate) fn pidfd_send_signal(pidfd: &std::fs::File, signal: libc::c_int) -> std::io::Result<()> {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            signal,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    if res == -1 {
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ESRCH) {
+            return Ok(());
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+pub(crate) fn pidfd_open(pid: u32) -> std::io::Result<std::fs::File> {
     let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
-    unsafe { std::fs::File::from_raw_fd(fd as i32) }
+    if fd == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { std::fs::File::from_raw_fd(fd as i32) })
 }
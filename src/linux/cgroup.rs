@@ -0,0 +1,237 @@
+use std::{
+    io::{Read as _, Seek as _},
+    os::fd::AsRawFd as _,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+/// A directory in the cgroup v2 hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    pub fn open(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        if !path.join("cgroup.procs").is_file() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{path:?} is not a cgroup v2 directory"),
+            ));
+        }
+        Ok(Self { path })
+    }
+
+    /// Opens the cgroup at `path` and creates it if it does not exist yet.
+    pub fn create(path: impl Into<PathBuf>) -> std::io::Result<Self> {
+        let path = path.into();
+        match std::fs::create_dir(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e),
+        }
+        Self::open(path)
+    }
+
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    pub fn add_process(&self, pid: libc::pid_t) -> std::io::Result<()> {
+        log::debug!("Move process {pid} into cgroup {:?}", self.path);
+        self.write("cgroup.procs", pid.to_string())
+    }
+
+    /// Moves the calling process into this cgroup.
+    pub fn enter(&self) -> std::io::Result<()> {
+        self.add_process(0)
+    }
+
+    /// Controllers available in this cgroup.
+    pub fn controllers(&self) -> std::io::Result<Vec<String>> {
+        Ok(self
+            .read("cgroup.controllers")?
+            .split_whitespace()
+            .map(str::to_string)
+            .collect())
+    }
+
+    /// Enables `controllers` for the children of this cgroup.
+    pub fn enable_controllers(&self, controllers: &[&str]) -> std::io::Result<()> {
+        if controllers.is_empty() {
+            return Ok(());
+        }
+        let enabled = self.read("cgroup.subtree_control")?;
+        let enabled = enabled.split_whitespace().collect::<Vec<_>>();
+        let missing = controllers
+            .iter()
+            .filter(|controller| !enabled.contains(controller))
+            .map(|controller| format!("+{controller}"))
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        log::debug!("Enable controllers {missing:?} in {:?}", self.path);
+        self.write("cgroup.subtree_control", missing.join(" "))
+    }
+
+    pub fn remove(self) -> std::io::Result<()> {
+        log::debug!("Remove cgroup {:?}", self.path);
+        std::fs::remove_dir(&self.path)
+    }
+
+    /// Sends `signal` to every process in this cgroup and its descendants. `SIGKILL` is delivered
+    /// through `cgroup.kill` if the kernel supports it.
+    pub fn signal_all(&self, signal: libc::c_int) -> std::io::Result<()> {
+        if signal == libc::SIGKILL {
+            match self.write("cgroup.kill", "1") {
+                Ok(()) => return Ok(()),
+                Err(e) => log::debug!("cgroup.kill not usable, fall back to cgroup.procs: {e}"),
+            }
+        }
+        for pid in self.processes()? {
+            super::kill(pid, signal)?;
+        }
+        Ok(())
+    }
+
+    /// All processes in this cgroup and its descendants.
+    pub fn processes(&self) -> std::io::Result<Vec<libc::pid_t>> {
+        let mut pids = self
+            .read("cgroup.procs")?
+            .lines()
+            .filter_map(|pid| pid.trim().parse().ok())
+            .collect::<Vec<_>>();
+        for entry in std::fs::read_dir(&self.path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                pids.extend(Self::open(entry.path())?.processes()?);
+            }
+        }
+        Ok(pids)
+    }
+
+    pub fn is_populated(&self) -> std::io::Result<bool> {
+        Ok(self.event("populated")? == Some(1))
+    }
+
+    /// Sends `SIGTERM` to all processes and `SIGKILL` to those which are still alive after
+    /// `timeout`.
+    pub fn stop(&self, timeout: Duration) -> std::io::Result<()> {
+        if !self.is_populated()? {
+            return Ok(());
+        }
+        log::debug!("Stop all processes in {:?}", self.path);
+        self.signal_all(libc::SIGTERM)?;
+        let unpopulated = |populated| populated == 0;
+        if self
+            .wait_for_event("populated", unpopulated, timeout)
+            .is_ok()
+        {
+            return Ok(());
+        }
+        log::warn!(
+            "Processes in {:?} did not stop in time, kill them",
+            self.path
+        );
+        self.signal_all(libc::SIGKILL)?;
+        self.wait_for_event("populated", unpopulated, timeout)
+    }
+
+    pub fn set_frozen(&self, frozen: bool, timeout: Duration) -> std::io::Result<()> {
+        log::debug!("Set frozen state of {:?} to {frozen}", self.path);
+        self.write("cgroup.freeze", if frozen { "1" } else { "0" })?;
+        let expected = frozen as u64;
+        self.wait_for_event("frozen", |value| value == expected, timeout)
+    }
+
+    pub fn is_frozen(&self) -> std::io::Result<bool> {
+        Ok(self.event("frozen")? == Some(1))
+    }
+
+    fn event(&self, key: &str) -> std::io::Result<Option<u64>> {
+        Ok(parse_event(&self.read("cgroup.events")?, key))
+    }
+
+    /// Waits until `key` in `cgroup.events` fulfills `condition`.
+    pub fn wait_for_event(
+        &self,
+        key: &str,
+        condition: impl Fn(u64) -> bool,
+        timeout: Duration,
+    ) -> std::io::Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut file = std::fs::File::open(self.path.join("cgroup.events"))?;
+        let mut content = String::new();
+        loop {
+            content.clear();
+            file.rewind()?;
+            file.read_to_string(&mut content)?;
+            if parse_event(&content, key).is_some_and(&condition) {
+                return Ok(());
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::TimedOut,
+                    format!("Timed out waiting for {key} in {:?}", self.path),
+                ));
+            }
+            let mut pollfd = libc::pollfd {
+                fd: file.as_raw_fd(),
+                events: libc::POLLPRI,
+                revents: 0,
+            };
+            let res = unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as i32) };
+            if res == -1 {
+                let err = std::io::Error::last_os_error();
+                if err.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    pub fn open_file(&self, file: &str) -> std::io::Result<std::fs::File> {
+        std::fs::File::open(self.path.join(file))
+    }
+
+    /// Registers a PSI trigger on the pressure file `file`. The returned file signals `POLLPRI`
+    /// whenever the trigger fires.
+    pub fn open_pressure_trigger(
+        &self,
+        file: &str,
+        trigger: &str,
+    ) -> std::io::Result<std::fs::File> {
+        use std::{io::Write as _, os::unix::fs::OpenOptionsExt as _};
+        log::debug!("Register pressure trigger {trigger:?} on {file}");
+        let mut pressure = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(self.path.join(file))?;
+        let mut trigger = trigger.as_bytes().to_vec();
+        trigger.push(0);
+        pressure.write_all(&trigger)?;
+        Ok(pressure)
+    }
+
+    pub(crate) fn write(&self, file: &str, value: impl AsRef<[u8]>) -> std::io::Result<()> {
+        std::fs::write(self.path.join(file), value)
+    }
+
+    pub(crate) fn read(&self, file: &str) -> std::io::Result<String> {
+        std::fs::read_to_string(self.path.join(file))
+    }
+}
+
+pub fn parse_event(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (k, value) = line.split_once(' ')?;
+        if k != key {
+            return None;
+        }
+        value.trim().parse().ok()
+    })
+}
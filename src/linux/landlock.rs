@@ -0,0 +1,172 @@
+//! Bindings for the Landlock LSM.
+
+use std::os::fd::{AsRawFd as _, FromRawFd as _, OwnedFd, RawFd};
+
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+const LANDLOCK_RULE_PATH_BENEATH: u32 = 1;
+const LANDLOCK_RULE_NET_PORT: u32 = 2;
+
+// File system rights are consecutive bits, newer ABIs add higher bits.
+pub const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+pub const ACCESS_FS_WRITE_FILE: u64 = 1 << 1;
+pub const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+pub const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+/// ABI 2
+pub const ACCESS_FS_REFER: u64 = 1 << 13;
+/// ABI 3
+pub const ACCESS_FS_TRUNCATE: u64 = 1 << 14;
+/// ABI 5
+pub const ACCESS_FS_IOCTL_DEV: u64 = 1 << 15;
+
+/// Rights which can be granted on files which are not directories.
+pub const ACCESS_FS_FILE: u64 = ACCESS_FS_EXECUTE
+    | ACCESS_FS_WRITE_FILE
+    | ACCESS_FS_READ_FILE
+    | ACCESS_FS_TRUNCATE
+    | ACCESS_FS_IOCTL_DEV;
+
+/// ABI 4
+pub const ACCESS_NET_BIND_TCP: u64 = 1 << 0;
+/// ABI 4
+pub const ACCESS_NET_CONNECT_TCP: u64 = 1 << 1;
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+    handled_access_net: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+#[repr(C)]
+struct NetPortAttr {
+    allowed_access: u64,
+    port: u64,
+}
+
+/// Version of the Landlock ABI supported by the kernel. `0` if Landlock is not supported or
+/// disabled.
+pub fn abi_version() -> u32 {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    if res < 0 {
+        return 0;
+    }
+    res as u32
+}
+
+/// File system rights known by `abi`.
+pub fn supported_fs_access(abi: u32) -> u64 {
+    match abi {
+        0 => 0,
+        1 => ACCESS_FS_REFER - 1,
+        2 => ACCESS_FS_TRUNCATE - 1,
+        3 | 4 => ACCESS_FS_IOCTL_DEV - 1,
+        _ => (ACCESS_FS_IOCTL_DEV << 1) - 1,
+    }
+}
+
+/// Network rights known by `abi`.
+pub fn supported_net_access(abi: u32) -> u64 {
+    match abi {
+        0..=3 => 0,
+        _ => ACCESS_NET_BIND_TCP | ACCESS_NET_CONNECT_TCP,
+    }
+}
+
+#[derive(Debug)]
+pub struct Ruleset {
+    fd: OwnedFd,
+}
+
+impl Ruleset {
+    /// Creates a ruleset which denies every handled access which is not allowed by a rule.
+    /// `handled_access_net` must be `0` on kernels older than ABI 4.
+    pub fn new(handled_access_fs: u64, handled_access_net: u64) -> std::io::Result<Self> {
+        let attr = RulesetAttr {
+            handled_access_fs,
+            handled_access_net,
+        };
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0,
+            )
+        };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd as RawFd) },
+        })
+    }
+
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(Self {
+            fd: self.fd.try_clone()?,
+        })
+    }
+
+    pub fn allow_path(&self, path: &std::path::Path, allowed_access: u64) -> std::io::Result<()> {
+        use std::os::unix::fs::OpenOptionsExt as _;
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+            .open(path)?;
+        let attr = PathBeneathAttr {
+            allowed_access,
+            parent_fd: file.as_raw_fd(),
+        };
+        self.add_rule(LANDLOCK_RULE_PATH_BENEATH, &attr as *const _ as *const _)
+    }
+
+    pub fn allow_port(&self, port: u16, allowed_access: u64) -> std::io::Result<()> {
+        let attr = NetPortAttr {
+            allowed_access,
+            port: port as u64,
+        };
+        self.add_rule(LANDLOCK_RULE_NET_PORT, &attr as *const _ as *const _)
+    }
+
+    fn add_rule(&self, rule_type: u32, attr: *const libc::c_void) -> std::io::Result<()> {
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                self.fd.as_raw_fd(),
+                rule_type,
+                attr,
+                0,
+            )
+        };
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Enforces the ruleset on the calling thread. `no_new_privs` is set before, because it is
+    /// required without `CAP_SYS_ADMIN`.
+    ///
+    /// Only async-signal-safe functions are used, so this can be called between fork and exec.
+    pub fn restrict_self(&self) -> std::io::Result<()> {
+        super::set_no_new_privs()?;
+        let res =
+            unsafe { libc::syscall(libc::SYS_landlock_restrict_self, self.fd.as_raw_fd(), 0) };
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
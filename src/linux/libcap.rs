@@ -1,31 +1,212 @@
+//! Capability management. Uses the raw syscalls by default, or libcap if the `libcap` feature
+//! is enabled.
+
+#[cfg(feature = "libcap")]
 #[allow(non_camel_case_types, non_upper_case_globals, unused)]
 mod capability;
+#[cfg(feature = "libcap")]
+mod linked;
+#[cfg(not(feature = "libcap"))]
+mod raw;
+
+#[cfg(feature = "libcap")]
+use linked as sys;
+#[cfg(not(feature = "libcap"))]
+use raw as sys;
 
-#[derive(Debug, Clone, Copy)]
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Capability {
+    CHOWN,
+    DAC_OVERRIDE,
+    DAC_READ_SEARCH,
+    FOWNER,
+    FSETID,
+    KILL,
     SETGID,
     SETUID,
+    SETPCAP,
+    LINUX_IMMUTABLE,
+    NET_BIND_SERVICE,
+    NET_BROADCAST,
+    NET_ADMIN,
+    NET_RAW,
+    IPC_LOCK,
+    IPC_OWNER,
+    SYS_MODULE,
+    SYS_RAWIO,
+    SYS_CHROOT,
+    SYS_PTRACE,
+    SYS_PACCT,
+    SYS_ADMIN,
+    SYS_BOOT,
+    SYS_NICE,
+    SYS_RESOURCE,
+    SYS_TIME,
+    SYS_TTY_CONFIG,
+    MKNOD,
+    LEASE,
+    AUDIT_WRITE,
+    AUDIT_CONTROL,
+    SETFCAP,
+    MAC_OVERRIDE,
+    MAC_ADMIN,
+    SYSLOG,
+    WAKE_ALARM,
+    BLOCK_SUSPEND,
+    AUDIT_READ,
+    PERFMON,
+    BPF,
+    CHECKPOINT_RESTORE,
 }
 
 impl Capability {
-    fn to_cap(&self) -> capability::cap_value_t {
+    pub const ALL: [Capability; 41] = [
+        Capability::CHOWN,
+        Capability::DAC_OVERRIDE,
+        Capability::DAC_READ_SEARCH,
+        Capability::FOWNER,
+        Capability::FSETID,
+        Capability::KILL,
+        Capability::SETGID,
+        Capability::SETUID,
+        Capability::SETPCAP,
+        Capability::LINUX_IMMUTABLE,
+        Capability::NET_BIND_SERVICE,
+        Capability::NET_BROADCAST,
+        Capability::NET_ADMIN,
+        Capability::NET_RAW,
+        Capability::IPC_LOCK,
+        Capability::IPC_OWNER,
+        Capability::SYS_MODULE,
+        Capability::SYS_RAWIO,
+        Capability::SYS_CHROOT,
+        Capability::SYS_PTRACE,
+        Capability::SYS_PACCT,
+        Capability::SYS_ADMIN,
+        Capability::SYS_BOOT,
+        Capability::SYS_NICE,
+        Capability::SYS_RESOURCE,
+        Capability::SYS_TIME,
+        Capability::SYS_TTY_CONFIG,
+        Capability::MKNOD,
+        Capability::LEASE,
+        Capability::AUDIT_WRITE,
+        Capability::AUDIT_CONTROL,
+        Capability::SETFCAP,
+        Capability::MAC_OVERRIDE,
+        Capability::MAC_ADMIN,
+        Capability::SYSLOG,
+        Capability::WAKE_ALARM,
+        Capability::BLOCK_SUSPEND,
+        Capability::AUDIT_READ,
+        Capability::PERFMON,
+        Capability::BPF,
+        Capability::CHECKPOINT_RESTORE,
+    ];
+
+    /// Number of the capability as used by the kernel.
+    fn value(self) -> u32 {
         match self {
-            Capability::SETGID => capability::CAP_SETGID as i32,
-            Capability::SETUID => capability::CAP_SETUID as i32,
+            Capability::CHOWN => 0,
+            Capability::DAC_OVERRIDE => 1,
+            Capability::DAC_READ_SEARCH => 2,
+            Capability::FOWNER => 3,
+            Capability::FSETID => 4,
+            Capability::KILL => 5,
+            Capability::SETGID => 6,
+            Capability::SETUID => 7,
+            Capability::SETPCAP => 8,
+            Capability::LINUX_IMMUTABLE => 9,
+            Capability::NET_BIND_SERVICE => 10,
+            Capability::NET_BROADCAST => 11,
+            Capability::NET_ADMIN => 12,
+            Capability::NET_RAW => 13,
+            Capability::IPC_LOCK => 14,
+            Capability::IPC_OWNER => 15,
+            Capability::SYS_MODULE => 16,
+            Capability::SYS_RAWIO => 17,
+            Capability::SYS_CHROOT => 18,
+            Capability::SYS_PTRACE => 19,
+            Capability::SYS_PACCT => 20,
+            Capability::SYS_ADMIN => 21,
+            Capability::SYS_BOOT => 22,
+            Capability::SYS_NICE => 23,
+            Capability::SYS_RESOURCE => 24,
+            Capability::SYS_TIME => 25,
+            Capability::SYS_TTY_CONFIG => 26,
+            Capability::MKNOD => 27,
+            Capability::LEASE => 28,
+            Capability::AUDIT_WRITE => 29,
+            Capability::AUDIT_CONTROL => 30,
+            Capability::SETFCAP => 31,
+            Capability::MAC_OVERRIDE => 32,
+            Capability::MAC_ADMIN => 33,
+            Capability::SYSLOG => 34,
+            Capability::WAKE_ALARM => 35,
+            Capability::BLOCK_SUSPEND => 36,
+            Capability::AUDIT_READ => 37,
+            Capability::PERFMON => 38,
+            Capability::BPF => 39,
+            Capability::CHECKPOINT_RESTORE => 40,
         }
     }
+
+    /// `false` if the running kernel does not know the capability.
+    pub fn is_supported(self) -> bool {
+        sys::is_supported(self.value())
+    }
 }
 
 pub fn has_capability(cap: Capability) -> bool {
-    let caps = unsafe { capability::cap_get_proc() };
-    let mut cap_value = 0;
-    unsafe {
-        capability::cap_get_flag(
-            caps,
-            cap.to_cap(),
-            capability::cap_flag_t_CAP_EFFECTIVE,
-            &mut cap_value,
-        )
-    };
-    cap_value == 1
+    sys::has_capability(cap.value())
+}
+
+/// Removes `cap` from the bounding set of the calling thread.
+pub(crate) fn drop_bounding(cap: Capability) -> std::io::Result<()> {
+    sys::drop_bounding(cap.value())
+}
+
+/// Replaces the effective, permitted and inheritable sets of the calling thread.
+pub(crate) fn set_proc(
+    effective: &[Capability],
+    permitted: &[Capability],
+    inheritable: &[Capability],
+) -> std::io::Result<()> {
+    let values = |set: &[Capability]| set.iter().map(|cap| cap.value()).collect::<Vec<_>>();
+    sys::set_proc(&values(effective), &values(permitted), &values(inheritable))
+}
+
+/// Replaces the ambient set of the calling thread. Every capability must be in the permitted
+/// and inheritable set.
+pub(crate) fn set_ambient(ambient: &[Capability]) -> std::io::Result<()> {
+    let values = ambient.iter().map(|cap| cap.value()).collect::<Vec<_>>();
+    sys::set_ambient(&values)
+}
+
+/// Keeps the permitted set of the calling thread if all of its user ids change from 0 to non
+/// zero. The effective and ambient sets are cleared anyway. Cleared on `execve`.
+pub(crate) fn set_keep_caps() -> std::io::Result<()> {
+    let res = unsafe { libc::prctl(libc::PR_SET_KEEPCAPS, 1, 0, 0, 0) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Capability sets which are restored right before the command is executed, because changing
+/// the user ids clears the effective and ambient sets.
+#[derive(Debug, Clone)]
+pub(crate) struct CapabilitySets {
+    pub effective: Vec<Capability>,
+    pub permitted: Vec<Capability>,
+    pub inheritable: Vec<Capability>,
+    pub ambient: Vec<Capability>,
+}
+
+impl CapabilitySets {
+    pub fn apply(&self) -> std::io::Result<()> {
+        set_proc(&self.effective, &self.permitted, &self.inheritable)?;
+        set_ambient(&self.ambient)
+    }
 }
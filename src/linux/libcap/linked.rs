@@ -0,0 +1,89 @@
+use super::capability;
+
+pub(super) fn is_supported(cap: u32) -> bool {
+    unsafe { capability::cap_get_bound(cap as capability::cap_value_t) >= 0 }
+}
+
+pub(super) fn has_capability(cap: u32) -> bool {
+    let caps = unsafe { capability::cap_get_proc() };
+    let mut cap_value = 0;
+    unsafe {
+        capability::cap_get_flag(
+            caps,
+            cap as capability::cap_value_t,
+            capability::cap_flag_t_CAP_EFFECTIVE,
+            &mut cap_value,
+        )
+    };
+    cap_value == 1
+}
+
+pub(super) fn drop_bounding(cap: u32) -> std::io::Result<()> {
+    let res = unsafe { capability::cap_drop_bound(cap as capability::cap_value_t) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(super) fn set_proc(
+    effective: &[u32],
+    permitted: &[u32],
+    inheritable: &[u32],
+) -> std::io::Result<()> {
+    let caps = unsafe { capability::cap_init() };
+    if caps.is_null() {
+        return Err(std::io::Error::last_os_error());
+    }
+    let res = [
+        (capability::cap_flag_t_CAP_EFFECTIVE, effective),
+        (capability::cap_flag_t_CAP_PERMITTED, permitted),
+        (capability::cap_flag_t_CAP_INHERITABLE, inheritable),
+    ]
+    .into_iter()
+    .filter(|(_, set)| !set.is_empty())
+    .try_for_each(|(flag, set)| {
+        let values = set
+            .iter()
+            .map(|cap| *cap as capability::cap_value_t)
+            .collect::<Vec<_>>();
+        let res = unsafe {
+            capability::cap_set_flag(
+                caps,
+                flag,
+                values.len() as i32,
+                values.as_ptr(),
+                capability::cap_flag_value_t_CAP_SET,
+            )
+        };
+        match res {
+            -1 => Err(std::io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    })
+    .and_then(|()| match unsafe { capability::cap_set_proc(caps) } {
+        -1 => Err(std::io::Error::last_os_error()),
+        _ => Ok(()),
+    });
+    unsafe { capability::cap_free(caps as *mut _) };
+    res
+}
+
+pub(super) fn set_ambient(ambient: &[u32]) -> std::io::Result<()> {
+    let res = unsafe { capability::cap_reset_ambient() };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    for cap in ambient {
+        let res = unsafe {
+            capability::cap_set_ambient(
+                *cap as capability::cap_value_t,
+                capability::cap_flag_value_t_CAP_SET,
+            )
+        };
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,106 @@
+//! Capability management using `capget`, `capset` and `prctl` directly.
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+/// Version 3 splits every set into two 32 bit words.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+fn header() -> CapUserHeader {
+    CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    }
+}
+
+fn capget() -> std::io::Result<[CapUserData; 2]> {
+    let mut header = header();
+    let mut data = [CapUserData::default(); 2];
+    let res = unsafe { libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(data)
+}
+
+fn capset(data: [CapUserData; 2]) -> std::io::Result<()> {
+    let mut header = header();
+    let res = unsafe { libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn prctl(option: libc::c_int, arg2: libc::c_ulong, arg3: libc::c_ulong) -> std::io::Result<i32> {
+    let res = unsafe { libc::prctl(option, arg2, arg3, 0, 0) };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(res)
+}
+
+/// Sets the bit of every capability in `caps` in the two words of a set.
+fn words(caps: &[u32]) -> [u32; 2] {
+    caps.iter().fold([0; 2], |mut words, cap| {
+        words[*cap as usize / 32] |= 1 << (cap % 32);
+        words
+    })
+}
+
+pub(super) fn is_supported(cap: u32) -> bool {
+    prctl(libc::PR_CAPBSET_READ, cap as libc::c_ulong, 0).is_ok()
+}
+
+pub(super) fn has_capability(cap: u32) -> bool {
+    capget()
+        .map(|data| data[cap as usize / 32].effective & (1 << (cap % 32)) != 0)
+        .unwrap_or(false)
+}
+
+pub(super) fn drop_bounding(cap: u32) -> std::io::Result<()> {
+    prctl(libc::PR_CAPBSET_DROP, cap as libc::c_ulong, 0)?;
+    Ok(())
+}
+
+pub(super) fn set_proc(
+    effective: &[u32],
+    permitted: &[u32],
+    inheritable: &[u32],
+) -> std::io::Result<()> {
+    let (effective, permitted, inheritable) =
+        (words(effective), words(permitted), words(inheritable));
+    let data = [0, 1].map(|i| CapUserData {
+        effective: effective[i],
+        permitted: permitted[i],
+        inheritable: inheritable[i],
+    });
+    capset(data)
+}
+
+pub(super) fn set_ambient(ambient: &[u32]) -> std::io::Result<()> {
+    prctl(
+        libc::PR_CAP_AMBIENT,
+        libc::PR_CAP_AMBIENT_CLEAR_ALL as libc::c_ulong,
+        0,
+    )?;
+    for cap in ambient {
+        prctl(
+            libc::PR_CAP_AMBIENT,
+            libc::PR_CAP_AMBIENT_RAISE as libc::c_ulong,
+            *cap as libc::c_ulong,
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,237 @@
+//! Bindings for the file descriptor based mount API.
+
+use std::{
+    ffi::CString,
+    os::{
+        fd::{AsRawFd as _, BorrowedFd, FromRawFd as _, OwnedFd, RawFd},
+        unix::ffi::OsStrExt as _,
+    },
+    path::Path,
+};
+
+const FSOPEN_CLOEXEC: libc::c_uint = 0x1;
+const FSCONFIG_SET_FLAG: libc::c_uint = 0;
+const FSCONFIG_SET_STRING: libc::c_uint = 1;
+const FSCONFIG_CMD_CREATE: libc::c_uint = 6;
+const FSMOUNT_CLOEXEC: libc::c_uint = 0x1;
+const OPEN_TREE_CLONE: libc::c_uint = 0x1;
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x4;
+const AT_RECURSIVE: libc::c_uint = 0x8000;
+
+pub const MOUNT_ATTR_RDONLY: u64 = 0x1;
+pub const MOUNT_ATTR_NOSUID: u64 = 0x2;
+pub const MOUNT_ATTR_NODEV: u64 = 0x4;
+pub const MOUNT_ATTR_NOEXEC: u64 = 0x8;
+pub const MOUNT_ATTR_NOATIME: u64 = 0x10;
+pub const MOUNT_ATTR_STRICTATIME: u64 = 0x20;
+pub const MOUNT_ATTR_NODIRATIME: u64 = 0x80;
+const MOUNT_ATTR_IDMAP: u64 = 0x0010_0000;
+
+#[repr(C)]
+struct MountAttr {
+    attr_set: u64,
+    attr_clr: u64,
+    propagation: u64,
+    userns_fd: u64,
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{operation} failed: {error}{}", messages.iter().map(|m| format!("; {m}")).collect::<String>())]
+pub struct MountApiError {
+    operation: &'static str,
+    error: std::io::Error,
+    /// Messages logged by the kernel for the file system context.
+    messages: Vec<String>,
+}
+
+impl MountApiError {
+    pub(crate) fn new(operation: &'static str, error: std::io::Error) -> Self {
+        Self {
+            operation,
+            error,
+            messages: Vec::new(),
+        }
+    }
+
+    fn last_os_error(operation: &'static str) -> Self {
+        Self {
+            operation,
+            error: std::io::Error::last_os_error(),
+            messages: Vec::new(),
+        }
+    }
+}
+
+fn c_string(operation: &'static str, bytes: &[u8]) -> Result<CString, MountApiError> {
+    CString::new(bytes).map_err(|error| MountApiError {
+        operation,
+        error: std::io::Error::new(std::io::ErrorKind::InvalidInput, error),
+        messages: Vec::new(),
+    })
+}
+
+/// File system context created by `fsopen`, which is configured and then mounted.
+#[derive(Debug)]
+pub struct FsContext {
+    fd: OwnedFd,
+}
+
+impl FsContext {
+    pub fn new(fs_type: &str) -> Result<Self, MountApiError> {
+        let fs_type = c_string("fsopen", fs_type.as_bytes())?;
+        let fd = unsafe { libc::syscall(libc::SYS_fsopen, fs_type.as_ptr(), FSOPEN_CLOEXEC) };
+        if fd == -1 {
+            return Err(MountApiError::last_os_error("fsopen"));
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd as RawFd) },
+        })
+    }
+
+    pub fn set_flag(&self, key: &str) -> Result<(), MountApiError> {
+        let key = c_string("fsconfig", key.as_bytes())?;
+        self.config(FSCONFIG_SET_FLAG, key.as_ptr(), std::ptr::null())
+    }
+
+    pub fn set_string(&self, key: &str, value: &[u8]) -> Result<(), MountApiError> {
+        let key = c_string("fsconfig", key.as_bytes())?;
+        let value = c_string("fsconfig", value)?;
+        self.config(FSCONFIG_SET_STRING, key.as_ptr(), value.as_ptr())
+    }
+
+    /// Creates the superblock and mounts it detached with `attributes`.
+    pub fn mount(&self, attributes: u64) -> Result<Mount, MountApiError> {
+        self.config(FSCONFIG_CMD_CREATE, std::ptr::null(), std::ptr::null())?;
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_fsmount,
+                self.fd.as_raw_fd(),
+                FSMOUNT_CLOEXEC,
+                attributes as libc::c_uint,
+            )
+        };
+        if fd == -1 {
+            return Err(self.error("fsmount"));
+        }
+        Ok(Mount {
+            fd: unsafe { OwnedFd::from_raw_fd(fd as RawFd) },
+        })
+    }
+
+    fn config(
+        &self,
+        cmd: libc::c_uint,
+        key: *const libc::c_char,
+        value: *const libc::c_char,
+    ) -> Result<(), MountApiError> {
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_fsconfig,
+                self.fd.as_raw_fd(),
+                cmd,
+                key,
+                value,
+                0 as libc::c_int,
+            )
+        };
+        if res == -1 {
+            return Err(self.error("fsconfig"));
+        }
+        Ok(())
+    }
+
+    /// Collects the messages the kernel logged for the context with the last error.
+    fn error(&self, operation: &'static str) -> MountApiError {
+        let mut error = MountApiError::last_os_error(operation);
+        let mut buf = [0_u8; 1024];
+        loop {
+            let len =
+                unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
+            if len <= 0 {
+                break;
+            }
+            let message = String::from_utf8_lossy(&buf[..len as usize]);
+            error.messages.push(message.trim_end().to_string());
+        }
+        error
+    }
+}
+
+/// Mount which is not attached to the file system yet.
+#[derive(Debug)]
+pub struct Mount {
+    fd: OwnedFd,
+}
+
+impl Mount {
+    /// Clones the mount at `path`, including its submounts if `recursive` is set.
+    pub fn open_tree(path: &Path, recursive: bool) -> Result<Self, MountApiError> {
+        let path = c_string("open_tree", path.as_os_str().as_bytes())?;
+        let mut flags = OPEN_TREE_CLONE | libc::O_CLOEXEC as libc::c_uint;
+        if recursive {
+            flags |= AT_RECURSIVE;
+        }
+        let fd =
+            unsafe { libc::syscall(libc::SYS_open_tree, libc::AT_FDCWD, path.as_ptr(), flags) };
+        if fd == -1 {
+            return Err(MountApiError::last_os_error("open_tree"));
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd as RawFd) },
+        })
+    }
+
+    /// Maps the ids of the mount with the id mappings of the user namespace `user_ns`. Requires
+    /// `CAP_SYS_ADMIN` in the user namespace owning the file system.
+    pub fn set_idmap(&self, user_ns: BorrowedFd, recursive: bool) -> Result<(), MountApiError> {
+        self.set_attr(
+            MountAttr {
+                attr_set: MOUNT_ATTR_IDMAP,
+                attr_clr: 0,
+                propagation: 0,
+                userns_fd: user_ns.as_raw_fd() as u64,
+            },
+            recursive,
+        )
+    }
+
+    fn set_attr(&self, attr: MountAttr, recursive: bool) -> Result<(), MountApiError> {
+        let mut flags = libc::AT_EMPTY_PATH as libc::c_uint;
+        if recursive {
+            flags |= AT_RECURSIVE;
+        }
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_mount_setattr,
+                self.fd.as_raw_fd(),
+                c"".as_ptr(),
+                flags,
+                &attr as *const MountAttr,
+                std::mem::size_of::<MountAttr>(),
+            )
+        };
+        if res == -1 {
+            return Err(MountApiError::last_os_error("mount_setattr"));
+        }
+        Ok(())
+    }
+
+    /// Attaches the mount to `target` in the mount namespace of the calling process.
+    pub fn attach(self, target: &Path) -> Result<(), MountApiError> {
+        let target = c_string("move_mount", target.as_os_str().as_bytes())?;
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_move_mount,
+                self.fd.as_raw_fd(),
+                c"".as_ptr(),
+                libc::AT_FDCWD,
+                target.as_ptr(),
+                MOVE_MOUNT_F_EMPTY_PATH,
+            )
+        };
+        if res == -1 {
+            return Err(MountApiError::last_os_error("move_mount"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+const NLMSG_ALIGNTO: usize = 4;
+const RECV_BUFFER_SIZE: usize = 8192;
+const VETH_INFO_PEER: u16 = 1;
+
+fn align(len: usize) -> usize {
+    (len + NLMSG_ALIGNTO - 1) & !(NLMSG_ALIGNTO - 1)
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct IfInfoMsg {
+    ifi_family: u8,
+    _pad: u8,
+    ifi_type: u16,
+    ifi_index: i32,
+    ifi_flags: u32,
+    ifi_change: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct IfAddrMsg {
+    ifa_family: u8,
+    ifa_prefixlen: u8,
+    ifa_flags: u8,
+    ifa_scope: u8,
+    ifa_index: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct RtMsg {
+    rtm_family: u8,
+    rtm_dst_len: u8,
+    rtm_src_len: u8,
+    rtm_tos: u8,
+    rtm_table: u8,
+    rtm_protocol: u8,
+    rtm_scope: u8,
+    rtm_type: u8,
+    rtm_flags: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+struct RtAttr {
+    rta_len: u16,
+    rta_type: u16,
+}
+
+fn family(addr: &std::net::IpAddr) -> u8 {
+    match addr {
+        std::net::IpAddr::V4(_) => libc::AF_INET as u8,
+        std::net::IpAddr::V6(_) => libc::AF_INET6 as u8,
+    }
+}
+
+fn octets(addr: &std::net::IpAddr) -> Vec<u8> {
+    match addr {
+        std::net::IpAddr::V4(addr) => addr.octets().to_vec(),
+        std::net::IpAddr::V6(addr) => addr.octets().to_vec(),
+    }
+}
+
+impl IfInfoMsg {
+    fn with_index(index: u32) -> Self {
+        Self {
+            ifi_family: libc::AF_UNSPEC as u8,
+            ifi_index: index as i32,
+            ..Default::default()
+        }
+    }
+}
+
+/// A rtnetlink socket bound to the network namespace of the calling process.
+#[derive(Debug)]
+pub struct Netlink {
+    fd: OwnedFd,
+    seq: u32,
+}
+
+impl Netlink {
+    pub fn new() -> std::io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                libc::NETLINK_ROUTE,
+            )
+        };
+        if fd == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const _ as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { fd, seq: 0 })
+    }
+
+    pub fn link_index(&self, name: &str) -> std::io::Result<u32> {
+        let name = std::ffi::CString::new(name)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if index == 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(index)
+    }
+
+    pub fn set_link_up(&mut self, index: u32) -> std::io::Result<()> {
+        log::debug!("Set link {index} up");
+        let msg = Message::new(
+            libc::RTM_NEWLINK,
+            0,
+            IfInfoMsg {
+                ifi_flags: libc::IFF_UP as u32,
+                ifi_change: libc::IFF_UP as u32,
+                ..IfInfoMsg::with_index(index)
+            },
+        );
+        self.request(msg)
+    }
+
+    /// Creates a veth pair whose peer end is placed directly into the network namespace of
+    /// `peer_pid`.
+    pub fn create_veth(
+        &mut self,
+        name: &str,
+        peer_name: &str,
+        peer_pid: libc::pid_t,
+    ) -> std::io::Result<()> {
+        log::debug!("Create veth pair {name} <-> {peer_name} (pid {peer_pid})");
+        let mut msg = Message::new(
+            libc::RTM_NEWLINK,
+            libc::NLM_F_CREATE | libc::NLM_F_EXCL,
+            IfInfoMsg::default(),
+        );
+        msg.attr_str(libc::IFLA_IFNAME, name);
+        let link_info = msg.begin_nested(libc::IFLA_LINKINFO);
+        msg.attr_str(libc::IFLA_INFO_KIND, "veth");
+        let info_data = msg.begin_nested(libc::IFLA_INFO_DATA);
+        let peer = msg.begin_nested(VETH_INFO_PEER);
+        msg.push(IfInfoMsg::default());
+        msg.attr_str(libc::IFLA_IFNAME, peer_name);
+        msg.attr(libc::IFLA_NET_NS_PID, &(peer_pid as u32).to_ne_bytes());
+        msg.end_nested(peer);
+        msg.end_nested(info_data);
+        msg.end_nested(link_info);
+        self.request(msg)
+    }
+
+    pub fn set_link_master(&mut self, index: u32, master: u32) -> std::io::Result<()> {
+        log::debug!("Attach link {index} to {master}");
+        let mut msg = Message::new(libc::RTM_NEWLINK, 0, IfInfoMsg::with_index(index));
+        msg.attr(libc::IFLA_MASTER, &master.to_ne_bytes());
+        self.request(msg)
+    }
+
+    pub fn add_address(
+        &mut self,
+        index: u32,
+        address: std::net::IpAddr,
+        prefix_len: u8,
+    ) -> std::io::Result<()> {
+        log::debug!("Add address {address}/{prefix_len} to link {index}");
+        let mut msg = Message::new(
+            libc::RTM_NEWADDR,
+            libc::NLM_F_CREATE | libc::NLM_F_EXCL,
+            IfAddrMsg {
+                ifa_family: family(&address),
+                ifa_prefixlen: prefix_len,
+                ifa_flags: if address.is_ipv6() {
+                    libc::IFA_F_NODAD as u8
+                } else {
+                    0
+                },
+                ifa_scope: libc::RT_SCOPE_UNIVERSE,
+                ifa_index: index,
+            },
+        );
+        let octets = octets(&address);
+        msg.attr(libc::IFA_LOCAL, &octets);
+        msg.attr(libc::IFA_ADDRESS, &octets);
+        self.request(msg)
+    }
+
+    pub fn add_default_route(
+        &mut self,
+        gateway: std::net::IpAddr,
+        index: u32,
+    ) -> std::io::Result<()> {
+        log::debug!("Add default route via {gateway} on link {index}");
+        let mut msg = Message::new(
+            libc::RTM_NEWROUTE,
+            libc::NLM_F_CREATE | libc::NLM_F_EXCL,
+            RtMsg {
+                rtm_family: family(&gateway),
+                rtm_table: libc::RT_TABLE_MAIN,
+                rtm_protocol: libc::RTPROT_BOOT,
+                rtm_scope: libc::RT_SCOPE_UNIVERSE,
+                rtm_type: libc::RTN_UNICAST,
+                ..Default::default()
+            },
+        );
+        msg.attr(libc::RTA_GATEWAY, &octets(&gateway));
+        msg.attr(libc::RTA_OIF, &index.to_ne_bytes());
+        self.request(msg)
+    }
+
+    fn request(&mut self, mut msg: Message) -> std::io::Result<()> {
+        self.seq = self.seq.wrapping_add(1);
+        let buf = msg.finish(self.seq);
+        let res =
+            unsafe { libc::send(self.fd.as_raw_fd(), buf.as_ptr() as *const _, buf.len(), 0) };
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        self.receive_ack()
+    }
+
+    fn receive_ack(&self) -> std::io::Result<()> {
+        let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+        loop {
+            let len = unsafe {
+                libc::recv(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut _,
+                    buf.len(),
+                    0,
+                )
+            };
+            if len == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            let mut offset = 0;
+            let len = len as usize;
+            while offset + std::mem::size_of::<libc::nlmsghdr>() <= len {
+                let header: libc::nlmsghdr =
+                    unsafe { std::ptr::read_unaligned(buf[offset..].as_ptr() as *const _) };
+                if header.nlmsg_seq == self.seq && header.nlmsg_type == libc::NLMSG_ERROR as u16 {
+                    let err: libc::nlmsgerr = unsafe {
+                        std::ptr::read_unaligned(
+                            buf[offset + std::mem::size_of::<libc::nlmsghdr>()..].as_ptr()
+                                as *const _,
+                        )
+                    };
+                    return match err.error {
+                        0 => Ok(()),
+                        e => Err(std::io::Error::from_raw_os_error(-e)),
+                    };
+                }
+                if header.nlmsg_len == 0 {
+                    break;
+                }
+                offset += align(header.nlmsg_len as usize);
+            }
+        }
+    }
+}
+
+struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    fn new<T: Copy>(kind: u16, flags: i32, payload: T) -> Self {
+        let mut msg = Self {
+            buf: vec![0; std::mem::size_of::<libc::nlmsghdr>()],
+        };
+        let header = libc::nlmsghdr {
+            nlmsg_len: 0,
+            nlmsg_type: kind,
+            nlmsg_flags: (libc::NLM_F_REQUEST | libc::NLM_F_ACK | flags) as u16,
+            nlmsg_seq: 0,
+            nlmsg_pid: 0,
+        };
+        msg.write_at(0, header);
+        msg.push(payload);
+        msg
+    }
+
+    fn push<T: Copy>(&mut self, value: T) {
+        let offset = self.buf.len();
+        self.buf.resize(offset + align(std::mem::size_of::<T>()), 0);
+        self.write_at(offset, value);
+    }
+
+    fn attr(&mut self, kind: u16, data: &[u8]) {
+        let offset = self.buf.len();
+        let len = std::mem::size_of::<RtAttr>() + data.len();
+        self.buf.resize(offset + align(len), 0);
+        self.write_at(
+            offset,
+            RtAttr {
+                rta_len: len as u16,
+                rta_type: kind,
+            },
+        );
+        let data_offset = offset + std::mem::size_of::<RtAttr>();
+        self.buf[data_offset..data_offset + data.len()].copy_from_slice(data);
+    }
+
+    fn attr_str(&mut self, kind: u16, value: &str) {
+        let mut data = value.as_bytes().to_vec();
+        data.push(0);
+        self.attr(kind, &data);
+    }
+
+    fn begin_nested(&mut self, kind: u16) -> usize {
+        let offset = self.buf.len();
+        self.attr(kind, &[]);
+        offset
+    }
+
+    fn end_nested(&mut self, offset: usize) {
+        let len = (self.buf.len() - offset) as u16;
+        self.write_at(offset, len);
+    }
+
+    fn write_at<T: Copy>(&mut self, offset: usize, value: T) {
+        assert!(offset + std::mem::size_of::<T>() <= self.buf.len());
+        unsafe { std::ptr::write_unaligned(self.buf[offset..].as_mut_ptr() as *mut T, value) };
+    }
+
+    fn finish(&mut self, seq: u32) -> &[u8] {
+        let len = self.buf.len() as u32;
+        self.write_at(0, len);
+        self.write_at(8, seq);
+        &self.buf
+    }
+}
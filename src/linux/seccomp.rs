@@ -0,0 +1,830 @@
+//! Compiler for seccomp policies into classic BPF programs.
+
+use std::os::{
+    fd::{AsRawFd as _, FromRawFd as _, OwnedFd},
+    unix::fs::FileExt as _,
+};
+
+const ARCH_OFFSET: u32 = 4;
+const NR_OFFSET: u32 = 0;
+const ARGS_OFFSET: u32 = 16;
+const MAX_JUMP: usize = u8::MAX as usize;
+const SECCOMP_RET_USER_NOTIF: u32 = 0x7fc0_0000;
+
+const SECCOMP_IOCTL_NOTIF_RECV: libc::c_ulong = 0xc050_2100;
+const SECCOMP_IOCTL_NOTIF_SEND: libc::c_ulong = 0xc018_2101;
+const SECCOMP_IOCTL_NOTIF_ID_VALID: libc::c_ulong = 0x4008_2102;
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_003e);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_00b7);
+#[cfg(target_arch = "riscv64")]
+const AUDIT_ARCH: Option<u32> = Some(0xc000_00f3);
+#[cfg(not(any(
+    target_arch = "x86_64",
+    target_arch = "aarch64",
+    target_arch = "riscv64"
+)))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// Syscalls of the x32 ABI have this bit set in their number on x86_64.
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Allow,
+    /// Fail the syscall with the given errno.
+    Errno(u16),
+    KillProcess,
+    KillThread,
+    /// Send `SIGSYS` to the calling thread.
+    Trap,
+    /// Allow the syscall and write it to the audit log.
+    Log,
+    /// Pass the syscall to the supervisor holding the [`Listener`] of the filter. Fails with
+    /// `ENOSYS` if the filter has no listener.
+    Notify,
+}
+
+impl Action {
+    fn ret(self) -> u32 {
+        match self {
+            Action::Allow => libc::SECCOMP_RET_ALLOW,
+            Action::Errno(errno) => libc::SECCOMP_RET_ERRNO | errno as u32,
+            Action::KillProcess => libc::SECCOMP_RET_KILL_PROCESS,
+            Action::KillThread => libc::SECCOMP_RET_KILL_THREAD,
+            Action::Trap => libc::SECCOMP_RET_TRAP,
+            Action::Log => libc::SECCOMP_RET_LOG,
+            Action::Notify => SECCOMP_RET_USER_NOTIF,
+        }
+    }
+}
+
+/// Comparison of a 64 bit syscall argument with a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Eq(u64),
+    Ne(u64),
+    Lt(u64),
+    Le(u64),
+    Gt(u64),
+    Ge(u64),
+    /// `arg & mask == value`
+    MaskedEq {
+        mask: u64,
+        value: u64,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArgCondition {
+    /// Index of the argument, `0..6`.
+    pub index: u8,
+    pub comparison: Comparison,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule {
+    syscall: libc::c_long,
+    action: Action,
+    conditions: Vec<ArgCondition>,
+}
+
+impl Rule {
+    pub fn new(syscall: libc::c_long, action: Action) -> Self {
+        Self {
+            syscall,
+            action,
+            conditions: Vec::new(),
+        }
+    }
+
+    /// Only match if argument `index` satisfies `comparison`. All conditions of a rule must
+    /// match.
+    pub fn when(mut self, index: u8, comparison: Comparison) -> Self {
+        self.conditions.push(ArgCondition { index, comparison });
+        self
+    }
+
+    fn compile(&self) -> Result<Vec<libc::sock_filter>, SeccompCompileError> {
+        let mut body = vec![ret(self.action)];
+        for condition in self.conditions.iter().rev() {
+            if condition.index >= 6 {
+                return Err(SeccompCompileError::InvalidArgument(condition.index));
+            }
+            let mut instructions = condition.compile(self.syscall, body.len())?;
+            instructions.append(&mut body);
+            body = instructions;
+        }
+        if body.len() > MAX_JUMP {
+            return Err(SeccompCompileError::RuleTooLarge(self.syscall));
+        }
+        let mut instructions = vec![
+            stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR_OFFSET),
+            jump(libc::BPF_JEQ, self.syscall as u32, 0, body.len() as u8),
+        ];
+        instructions.append(&mut body);
+        Ok(instructions)
+    }
+}
+
+/// Jump target inside of a compiled argument condition.
+#[derive(Clone, Copy)]
+enum Target {
+    /// The next instruction.
+    Next,
+    /// The first instruction after the condition.
+    Pass,
+    /// The first instruction after the rule.
+    Fail,
+}
+
+enum Instruction {
+    Load(u32),
+    And(u32),
+    Jump(u32, u32, Target, Target),
+}
+
+impl ArgCondition {
+    /// `tail` is the number of instructions between the end of this condition and the end of
+    /// the rule.
+    fn compile(
+        &self,
+        syscall: libc::c_long,
+        tail: usize,
+    ) -> Result<Vec<libc::sock_filter>, SeccompCompileError> {
+        use Instruction::*;
+        use Target::*;
+
+        let low = ARGS_OFFSET + self.index as u32 * 8;
+        let high = low + 4;
+        let split = |value: u64| ((value >> 32) as u32, value as u32);
+        let instructions = match self.comparison {
+            Comparison::Eq(value) => {
+                let (hi, lo) = split(value);
+                vec![
+                    Load(high),
+                    Jump(libc::BPF_JEQ, hi, Next, Fail),
+                    Load(low),
+                    Jump(libc::BPF_JEQ, lo, Pass, Fail),
+                ]
+            }
+            Comparison::Ne(value) => {
+                let (hi, lo) = split(value);
+                vec![
+                    Load(high),
+                    Jump(libc::BPF_JEQ, hi, Next, Pass),
+                    Load(low),
+                    Jump(libc::BPF_JEQ, lo, Fail, Pass),
+                ]
+            }
+            Comparison::MaskedEq { mask, value } => {
+                let (mask_hi, mask_lo) = split(mask);
+                let (hi, lo) = split(value & mask);
+                vec![
+                    Load(high),
+                    And(mask_hi),
+                    Jump(libc::BPF_JEQ, hi, Next, Fail),
+                    Load(low),
+                    And(mask_lo),
+                    Jump(libc::BPF_JEQ, lo, Pass, Fail),
+                ]
+            }
+            Comparison::Gt(value) | Comparison::Ge(value) => {
+                let (hi, lo) = split(value);
+                let op = match self.comparison {
+                    Comparison::Gt(_) => libc::BPF_JGT,
+                    _ => libc::BPF_JGE,
+                };
+                vec![
+                    Load(high),
+                    Jump(libc::BPF_JGT, hi, Pass, Next),
+                    Jump(libc::BPF_JEQ, hi, Next, Fail),
+                    Load(low),
+                    Jump(op, lo, Pass, Fail),
+                ]
+            }
+            Comparison::Lt(value) | Comparison::Le(value) => {
+                let (hi, lo) = split(value);
+                let op = match self.comparison {
+                    Comparison::Lt(_) => libc::BPF_JGE,
+                    _ => libc::BPF_JGT,
+                };
+                vec![
+                    Load(high),
+                    Jump(libc::BPF_JGE, hi, Next, Pass),
+                    Jump(libc::BPF_JEQ, hi, Next, Fail),
+                    Load(low),
+                    Jump(op, lo, Fail, Pass),
+                ]
+            }
+        };
+
+        let len = instructions.len();
+        let offset = |position: usize, target: Target| {
+            let offset = match target {
+                Next => 0,
+                Pass => len - position - 1,
+                Fail => len - position - 1 + tail,
+            };
+            u8::try_from(offset).map_err(|_| SeccompCompileError::RuleTooLarge(syscall))
+        };
+        instructions
+            .into_iter()
+            .enumerate()
+            .map(|(position, instruction)| match instruction {
+                Load(offset) => Ok(stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, offset)),
+                And(mask) => Ok(stmt(libc::BPF_ALU | libc::BPF_AND | libc::BPF_K, mask)),
+                Jump(op, k, jt, jf) => {
+                    Ok(jump(op, k, offset(position, jt)?, offset(position, jf)?))
+                }
+            })
+            .collect()
+    }
+}
+
+/// A seccomp policy. Rules are checked in the order they were added and the action of the
+/// first matching rule is taken. Syscalls of a foreign architecture are killed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Policy {
+    default_action: Action,
+    rules: Vec<Rule>,
+}
+
+impl Policy {
+    pub fn new(default_action: Action) -> Self {
+        Self {
+            default_action,
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn allow(self, syscall: libc::c_long) -> Self {
+        self.rule(Rule::new(syscall, Action::Allow))
+    }
+
+    pub fn errno(self, syscall: libc::c_long, errno: u16) -> Self {
+        self.rule(Rule::new(syscall, Action::Errno(errno)))
+    }
+
+    pub fn compile(&self) -> Result<Program, SeccompCompileError> {
+        let arch = AUDIT_ARCH.ok_or(SeccompCompileError::UnsupportedArch)?;
+        let kill = ret(Action::KillProcess);
+        let mut filter = vec![
+            stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH_OFFSET),
+            jump(libc::BPF_JEQ, arch, 1, 0),
+            kill,
+        ];
+        #[cfg(target_arch = "x86_64")]
+        filter.extend([
+            stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR_OFFSET),
+            jump(libc::BPF_JGE, X32_SYSCALL_BIT, 0, 1),
+            kill,
+        ]);
+        for rule in &self.rules {
+            filter.extend(rule.compile()?);
+        }
+        filter.push(ret(self.default_action));
+        if filter.len() > libc::BPF_MAXINSNS as usize {
+            return Err(SeccompCompileError::ProgramTooLarge(filter.len()));
+        }
+        Ok(Program { filter })
+    }
+}
+
+/// A compiled seccomp filter.
+#[derive(Clone)]
+pub struct Program {
+    filter: Vec<libc::sock_filter>,
+}
+
+impl std::fmt::Debug for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Program")
+            .field("instructions", &self.filter.len())
+            .finish()
+    }
+}
+
+impl Program {
+    /// Installs the filter for the calling thread. `no_new_privs` is set before, because it is
+    /// required to install a filter without `CAP_SYS_ADMIN`.
+    ///
+    /// Only async-signal-safe functions are used, so this can be called between fork and exec.
+    pub fn install(&self) -> std::io::Result<()> {
+        self.install_with_flags(0)?;
+        Ok(())
+    }
+
+    /// Installs the filter like [`Self::install`] and returns a listener for syscalls with the
+    /// [`Action::Notify`] action.
+    pub fn install_with_listener(&self) -> std::io::Result<Listener> {
+        let fd = self.install_with_flags(libc::SECCOMP_FILTER_FLAG_NEW_LISTENER)?;
+        Ok(Listener {
+            fd: unsafe { OwnedFd::from_raw_fd(fd as i32) },
+        })
+    }
+
+    fn install_with_flags(&self, flags: libc::c_ulong) -> std::io::Result<libc::c_long> {
+        super::set_no_new_privs()?;
+        let prog = libc::sock_fprog {
+            len: self.filter.len() as libc::c_ushort,
+            filter: self.filter.as_ptr() as *mut _,
+        };
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_seccomp,
+                libc::SECCOMP_SET_MODE_FILTER,
+                flags,
+                &prog as *const libc::sock_fprog,
+            )
+        };
+        if res == -1 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(res)
+    }
+}
+
+/// Receives syscalls of processes which hit a rule with [`Action::Notify`].
+#[derive(Debug)]
+pub struct Listener {
+    fd: OwnedFd,
+}
+
+impl Listener {
+    /// Waits for the next notification. Returns `None` if the calling process was interrupted
+    /// or died before the notification could be received.
+    pub fn receive(&self) -> std::io::Result<Option<Notification<'_>>> {
+        let mut notif: libc::seccomp_notif = unsafe { std::mem::zeroed() };
+        let res = unsafe { libc::ioctl(self.fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_RECV, &mut notif) };
+        if res == -1 {
+            let err = std::io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::ENOENT) | Some(libc::EINTR) => Ok(None),
+                _ => Err(err),
+            };
+        }
+        Ok(Some(Notification {
+            listener: self,
+            id: notif.id,
+            pid: notif.pid as libc::pid_t,
+            syscall: notif.data.nr as libc::c_long,
+            args: notif.data.args,
+        }))
+    }
+
+    /// Answers the notification with `id`. Answering a notification whose process died in the
+    /// meantime is not an error.
+    pub fn respond(&self, id: u64, response: Response) -> std::io::Result<()> {
+        let mut resp = libc::seccomp_notif_resp {
+            id,
+            val: 0,
+            error: 0,
+            flags: 0,
+        };
+        match response {
+            Response::Continue => resp.flags = libc::SECCOMP_USER_NOTIF_FLAG_CONTINUE as u32,
+            Response::Error(errno) => resp.error = -errno,
+            Response::Return(val) => resp.val = val,
+        }
+        let res = unsafe { libc::ioctl(self.fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_SEND, &mut resp) };
+        if res == -1 {
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() == Some(libc::ENOENT) {
+                return Ok(());
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn is_valid(&self, id: u64) -> bool {
+        let mut id = id;
+        let res =
+            unsafe { libc::ioctl(self.fd.as_raw_fd(), SECCOMP_IOCTL_NOTIF_ID_VALID, &mut id) };
+        res == 0
+    }
+}
+
+impl std::os::fd::AsRawFd for Listener {
+    fn as_raw_fd(&self) -> std::os::fd::RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// A syscall waiting for a [`Response`] of the supervisor.
+#[derive(Debug)]
+pub struct Notification<'a> {
+    listener: &'a Listener,
+    pub id: u64,
+    pub pid: libc::pid_t,
+    pub syscall: libc::c_long,
+    pub args: [u64; 6],
+}
+
+impl Notification<'_> {
+    /// Still `true` if the process is waiting for the response. The pid of the notification
+    /// might have been reused if this returns `false`.
+    pub fn is_valid(&self) -> bool {
+        self.listener.is_valid(self.id)
+    }
+
+    /// Reads a NUL terminated string of at most `PATH_MAX` bytes at `address` from the memory
+    /// of the calling process.
+    pub fn read_c_string(&self, address: u64) -> std::io::Result<std::ffi::CString> {
+        const CHUNK_SIZE: u64 = 256;
+        let mem = std::fs::File::open(format!("/proc/{}/mem", self.pid))?;
+        let mut buf = Vec::new();
+        let mut offset = address;
+        while buf.len() < libc::PATH_MAX as usize {
+            // Reads must not cross into a page which might not be mapped
+            let page_end = (offset / 4096 + 1) * 4096;
+            let mut chunk = vec![0; (page_end - offset).min(CHUNK_SIZE) as usize];
+            let len = mem.read_at(&mut chunk, offset)?;
+            if len == 0 {
+                break;
+            }
+            if let Some(end) = chunk[..len].iter().position(|b| *b == 0) {
+                buf.extend_from_slice(&chunk[..end]);
+                // The memory could belong to a different process if the pid was reused
+                if !self.is_valid() {
+                    return Err(std::io::Error::from_raw_os_error(libc::ESRCH));
+                }
+                return Ok(std::ffi::CString::new(buf).expect("String contains no NUL"));
+            }
+            buf.extend_from_slice(&chunk[..len]);
+            offset += len as u64;
+        }
+        Err(std::io::Error::from_raw_os_error(libc::ENAMETOOLONG))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Response {
+    /// Let the kernel execute the syscall.
+    Continue,
+    /// Fail the syscall with the given errno.
+    Error(i32),
+    /// Return a value without executing the syscall.
+    Return(i64),
+}
+
+fn stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(op: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: (libc::BPF_JMP | op | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+fn ret(action: Action) -> libc::sock_filter {
+    stmt(libc::BPF_RET | libc::BPF_K, action.ret())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SeccompCompileError {
+    #[error("Seccomp filters are not supported on this architecture")]
+    UnsupportedArch,
+    #[error("Syscalls only have 6 arguments, got argument index {0}")]
+    InvalidArgument(u8),
+    #[error("Rule for syscall {0} has too many conditions")]
+    RuleTooLarge(libc::c_long),
+    #[error("Seccomp program has {0} instructions, only 4096 are allowed")]
+    ProgramTooLarge(usize),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYSCALL: libc::c_long = 42;
+    const OTHER_SYSCALL: libc::c_long = 43;
+    const ERRNO: Action = Action::Errno(libc::EPERM as u16);
+
+    /// Runs `program` like the kernel would for a syscall and returns the result.
+    fn run(program: &Program, arch: u32, nr: u32, args: [u64; 6]) -> u32 {
+        let load = |offset: u32| match offset {
+            NR_OFFSET => nr,
+            ARCH_OFFSET => arch,
+            _ => {
+                let index = ((offset - ARGS_OFFSET) / 8) as usize;
+                if (offset - ARGS_OFFSET) % 8 == 0 {
+                    args[index] as u32
+                } else {
+                    (args[index] >> 32) as u32
+                }
+            }
+        };
+        let mut accumulator = 0;
+        let mut pc = 0;
+        loop {
+            let instruction = program.filter[pc];
+            let code = instruction.code as u32;
+            pc += 1;
+            match code {
+                _ if code == libc::BPF_LD | libc::BPF_W | libc::BPF_ABS => {
+                    accumulator = load(instruction.k)
+                }
+                _ if code == libc::BPF_ALU | libc::BPF_AND | libc::BPF_K => {
+                    accumulator &= instruction.k
+                }
+                _ if code == libc::BPF_RET | libc::BPF_K => return instruction.k,
+                _ if code & 0x07 == libc::BPF_JMP => {
+                    let taken = match code & 0xf0 {
+                        libc::BPF_JEQ => accumulator == instruction.k,
+                        libc::BPF_JGT => accumulator > instruction.k,
+                        libc::BPF_JGE => accumulator >= instruction.k,
+                        _ => panic!("Unexpected jump {code:#x}"),
+                    };
+                    pc += if taken {
+                        instruction.jt
+                    } else {
+                        instruction.jf
+                    } as usize;
+                }
+                _ => panic!("Unexpected instruction {code:#x}"),
+            }
+        }
+    }
+
+    fn run_native(program: &Program, nr: libc::c_long, args: [u64; 6]) -> u32 {
+        run(program, AUDIT_ARCH.unwrap(), nr as u32, args)
+    }
+
+    fn matches(comparison: Comparison, arg: u64) -> bool {
+        let program = Policy::new(ERRNO)
+            .rule(Rule::new(SYSCALL, Action::Allow).when(1, comparison))
+            .compile()
+            .unwrap();
+        let result = run_native(&program, SYSCALL, [0, arg, 0, 0, 0, 0]);
+        assert!(result == Action::Allow.ret() || result == ERRNO.ret());
+        result == Action::Allow.ret()
+    }
+
+    fn instruction(code: u32, jt: u8, jf: u8, k: u32) -> (u16, u8, u8, u32) {
+        (code as u16, jt, jf, k)
+    }
+
+    fn instructions(program: &Program) -> Vec<(u16, u8, u8, u32)> {
+        program
+            .filter
+            .iter()
+            .map(|i| (i.code, i.jt, i.jf, i.k))
+            .collect()
+    }
+
+    /// Number of instructions before the first rule.
+    fn header_len() -> usize {
+        Policy::new(Action::Allow).compile().unwrap().filter.len() - 1
+    }
+
+    #[test]
+    fn foreign_arch_is_killed() {
+        let program = Policy::new(Action::Allow).compile().unwrap();
+        assert_eq!(
+            run(&program, 0x4000_0003, SYSCALL as u32, [0; 6]),
+            Action::KillProcess.ret()
+        );
+        assert_eq!(run_native(&program, SYSCALL, [0; 6]), Action::Allow.ret());
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn x32_syscalls_are_killed() {
+        let program = Policy::new(Action::Allow).compile().unwrap();
+        assert_eq!(
+            run_native(&program, X32_SYSCALL_BIT as libc::c_long | SYSCALL, [0; 6]),
+            Action::KillProcess.ret()
+        );
+    }
+
+    #[test]
+    fn rule_instructions() {
+        let program = Policy::new(ERRNO)
+            .rule(Rule::new(SYSCALL, Action::Allow).when(2, Comparison::Eq(7)))
+            .compile()
+            .unwrap();
+        let load = libc::BPF_LD | libc::BPF_W | libc::BPF_ABS;
+        let jeq = libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K;
+        let ret = libc::BPF_RET | libc::BPF_K;
+        assert_eq!(
+            instructions(&program)[header_len()..],
+            [
+                instruction(load, 0, 0, NR_OFFSET),
+                // Skips the 4 instructions of the condition and the return of the rule
+                instruction(jeq, 0, 5, SYSCALL as u32),
+                instruction(load, 0, 0, ARGS_OFFSET + 2 * 8 + 4),
+                instruction(jeq, 0, 3, 0),
+                instruction(load, 0, 0, ARGS_OFFSET + 2 * 8),
+                instruction(jeq, 0, 1, 7),
+                instruction(ret, 0, 0, libc::SECCOMP_RET_ALLOW),
+                instruction(ret, 0, 0, libc::SECCOMP_RET_ERRNO | libc::EPERM as u32),
+            ]
+        );
+    }
+
+    #[test]
+    fn unconditional_rule() {
+        let program = Policy::new(ERRNO).allow(SYSCALL).compile().unwrap();
+        assert_eq!(run_native(&program, SYSCALL, [0; 6]), Action::Allow.ret());
+        assert_eq!(run_native(&program, OTHER_SYSCALL, [0; 6]), ERRNO.ret());
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let program = Policy::new(Action::Allow)
+            .rule(Rule::new(SYSCALL, Action::Log).when(0, Comparison::Eq(1)))
+            .errno(SYSCALL, libc::EPERM as u16)
+            .rule(Rule::new(SYSCALL, Action::Trap))
+            .compile()
+            .unwrap();
+        assert_eq!(
+            run_native(&program, SYSCALL, [1, 0, 0, 0, 0, 0]),
+            Action::Log.ret()
+        );
+        assert_eq!(
+            run_native(&program, SYSCALL, [2, 0, 0, 0, 0, 0]),
+            ERRNO.ret()
+        );
+        assert_eq!(
+            run_native(&program, OTHER_SYSCALL, [1, 0, 0, 0, 0, 0]),
+            Action::Allow.ret()
+        );
+    }
+
+    #[test]
+    fn all_conditions_must_match() {
+        let program = Policy::new(ERRNO)
+            .rule(
+                Rule::new(SYSCALL, Action::Allow)
+                    .when(0, Comparison::Eq(1))
+                    .when(5, Comparison::Gt(10)),
+            )
+            .compile()
+            .unwrap();
+        assert_eq!(
+            run_native(&program, SYSCALL, [1, 0, 0, 0, 0, 11]),
+            Action::Allow.ret()
+        );
+        assert_eq!(
+            run_native(&program, SYSCALL, [1, 0, 0, 0, 0, 10]),
+            ERRNO.ret()
+        );
+        assert_eq!(
+            run_native(&program, SYSCALL, [2, 0, 0, 0, 0, 11]),
+            ERRNO.ret()
+        );
+    }
+
+    const HIGH: u64 = 0x1_0000_0005;
+
+    #[test]
+    fn eq() {
+        assert!(matches(Comparison::Eq(HIGH), HIGH));
+        assert!(!matches(Comparison::Eq(HIGH), 5));
+        assert!(!matches(Comparison::Eq(HIGH), HIGH + 1));
+        assert!(!matches(Comparison::Eq(5), HIGH));
+    }
+
+    #[test]
+    fn ne() {
+        assert!(!matches(Comparison::Ne(HIGH), HIGH));
+        assert!(matches(Comparison::Ne(HIGH), 5));
+        assert!(matches(Comparison::Ne(HIGH), HIGH + 1));
+        assert!(matches(Comparison::Ne(5), HIGH));
+    }
+
+    #[test]
+    fn lt() {
+        assert!(matches(Comparison::Lt(HIGH), HIGH - 1));
+        assert!(matches(Comparison::Lt(HIGH), 6));
+        assert!(!matches(Comparison::Lt(HIGH), HIGH));
+        assert!(!matches(Comparison::Lt(HIGH), HIGH + 1));
+        assert!(!matches(Comparison::Lt(HIGH), 0x2_0000_0000));
+    }
+
+    #[test]
+    fn le() {
+        assert!(matches(Comparison::Le(HIGH), HIGH - 1));
+        assert!(matches(Comparison::Le(HIGH), HIGH));
+        assert!(matches(Comparison::Le(HIGH), 6));
+        assert!(!matches(Comparison::Le(HIGH), HIGH + 1));
+        assert!(!matches(Comparison::Le(HIGH), 0x2_0000_0000));
+    }
+
+    #[test]
+    fn gt() {
+        assert!(!matches(Comparison::Gt(HIGH), HIGH - 1));
+        assert!(!matches(Comparison::Gt(HIGH), HIGH));
+        assert!(!matches(Comparison::Gt(HIGH), 6));
+        assert!(matches(Comparison::Gt(HIGH), HIGH + 1));
+        assert!(matches(Comparison::Gt(HIGH), 0x2_0000_0000));
+    }
+
+    #[test]
+    fn ge() {
+        assert!(!matches(Comparison::Ge(HIGH), HIGH - 1));
+        assert!(matches(Comparison::Ge(HIGH), HIGH));
+        assert!(!matches(Comparison::Ge(HIGH), 6));
+        assert!(matches(Comparison::Ge(HIGH), HIGH + 1));
+        assert!(matches(Comparison::Ge(HIGH), 0x2_0000_0000));
+    }
+
+    #[test]
+    fn masked_eq() {
+        let comparison = Comparison::MaskedEq {
+            mask: 0xff_0000_00f0,
+            value: 0x12_0000_0030,
+        };
+        assert!(matches(comparison, 0x12_0000_0030));
+        assert!(matches(comparison, 0xff12_ffff_ff3f));
+        assert!(!matches(comparison, 0x13_0000_0030));
+        assert!(!matches(comparison, 0x12_0000_0040));
+        assert!(!matches(comparison, 0x30));
+    }
+
+    #[test]
+    fn program_longer_than_a_jump() {
+        let policy = (0..200).fold(Policy::new(ERRNO), |policy, nr| {
+            policy.rule(Rule::new(1000 + nr, Action::Allow).when(0, Comparison::Eq(nr as u64)))
+        });
+        let program = policy.compile().unwrap();
+        assert!(program.filter.len() > 4 * MAX_JUMP);
+        for nr in [0, 100, 199] {
+            assert_eq!(
+                run_native(&program, 1000 + nr, [nr as u64, 0, 0, 0, 0, 0]),
+                Action::Allow.ret()
+            );
+            assert_eq!(
+                run_native(&program, 1000 + nr, [nr as u64 + 1, 0, 0, 0, 0, 0]),
+                ERRNO.ret()
+            );
+        }
+        assert_eq!(
+            run_native(&program, 1200, [200, 0, 0, 0, 0, 0]),
+            ERRNO.ret()
+        );
+    }
+
+    #[test]
+    fn longest_rule() {
+        // Every condition has 4 instructions, one instruction returns the action
+        let rule = |conditions: u64| {
+            (0..conditions).fold(Rule::new(SYSCALL, Action::Allow), |rule, value| {
+                rule.when((value % 6) as u8, Comparison::Ne(value + 100))
+            })
+        };
+        let longest = (MAX_JUMP as u64 - 1) / 4;
+        let program = Policy::new(ERRNO).rule(rule(longest)).compile().unwrap();
+        let jump = program.filter[header_len() + 1];
+        assert_eq!(jump.jf as u64, longest * 4 + 1);
+        assert_eq!(run_native(&program, SYSCALL, [0; 6]), Action::Allow.ret());
+        assert_eq!(
+            run_native(&program, SYSCALL, [0, 0, 0, 0, 0, 5 + 100]),
+            ERRNO.ret()
+        );
+        assert_eq!(run_native(&program, OTHER_SYSCALL, [0; 6]), ERRNO.ret());
+
+        assert!(matches!(
+            Policy::new(ERRNO).rule(rule(longest + 1)).compile(),
+            Err(SeccompCompileError::RuleTooLarge(SYSCALL))
+        ));
+    }
+
+    #[test]
+    fn invalid_argument() {
+        assert!(matches!(
+            Policy::new(ERRNO)
+                .rule(Rule::new(SYSCALL, Action::Allow).when(6, Comparison::Eq(0)))
+                .compile(),
+            Err(SeccompCompileError::InvalidArgument(6))
+        ));
+    }
+
+    #[test]
+    fn program_too_large() {
+        let policy = (0..2100).fold(Policy::new(ERRNO), |policy, nr| policy.allow(nr));
+        assert!(matches!(
+            policy.compile(),
+            Err(SeccompCompileError::ProgramTooLarge(_))
+        ));
+    }
+}